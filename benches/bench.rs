@@ -1,4 +1,6 @@
 #![feature(test)]
+// Sizes like `1 * KIB` are written that way to line up with their neighbors.
+#![allow(clippy::identity_op)]
 
 extern crate test;
 
@@ -53,7 +55,7 @@ fn bench_single_compression_fn(b: &mut Bencher, platform: Platform) {
     let mut state = [1u32; 8];
     let mut r = RandomInput::new(b, 64);
     let input = array_ref!(r.get(), 0, 64);
-    b.iter(|| platform.compress_in_place(&mut state, input, 64 as u8, 0, 0));
+    b.iter(|| platform.compress_in_place(&mut state, input, 64, 0, 0));
 }
 
 #[bench]
@@ -178,6 +180,11 @@ fn bench_many_parents_fn(b: &mut Bencher, platform: Platform) {
     });
 }
 
+#[bench]
+fn bench_many_parents_portable(b: &mut Bencher) {
+    bench_many_parents_fn(b, Platform::portable());
+}
+
 #[bench]
 #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
 fn bench_many_parents_sse2(b: &mut Bencher) {
@@ -218,6 +225,14 @@ fn bench_many_parents_neon(b: &mut Bencher) {
     }
 }
 
+// There's no SME (Arm's Scalable Matrix Extension, also on Apple M4) backend, and it's unlikely
+// to beat NEON for parents. The compression function is 32-bit adds, XORs, and rotations with no
+// multiplications, so the SME outer-product instructions don't apply, and what's left is SVE in
+// streaming mode. That has the same 128-bit lanes on Apple chips, runs on a shared coprocessor
+// with higher latency, and needs an SMSTART/SMSTOP pair around every call, which is expensive
+// next to the handful of parent compressions in each call here. Anyone trying it should compare
+// against the NEON and portable benchmarks above, and run test_hash_many_fn for correctness.
+
 fn bench_atonce(b: &mut Bencher, len: usize) {
     let mut input = RandomInput::new(b, len);
     b.iter(|| blake3::hash(input.get()));
@@ -228,6 +243,33 @@ fn bench_atonce_0001_block(b: &mut Bencher) {
     bench_atonce(b, BLOCK_LEN);
 }
 
+// Hash every input length from 0 to BLOCK_LEN, each of which takes a single
+// compression.
+#[bench]
+fn bench_atonce_short_inputs(b: &mut Bencher) {
+    let mut input = RandomInput::new(b, BLOCK_LEN);
+    b.bytes = (0..=BLOCK_LEN as u64).sum();
+    b.iter(|| {
+        let input = input.get();
+        for len in 0..=BLOCK_LEN {
+            test::black_box(blake3::hash(&input[..len]));
+        }
+    });
+}
+
+// Many tiny messages, where the per-call overhead of hash(), including
+// platform detection, is a large fraction of the total.
+#[bench]
+fn bench_atonce_many_16_byte_messages(b: &mut Bencher) {
+    const COUNT: usize = 64;
+    let mut input = RandomInput::new(b, 16 * COUNT);
+    b.iter(|| {
+        for message in input.get().chunks_exact(16) {
+            test::black_box(blake3::hash(message));
+        }
+    });
+}
+
 #[bench]
 fn bench_atonce_0001_kib(b: &mut Bencher) {
     bench_atonce(b, 1 * KIB);
@@ -348,6 +390,21 @@ fn bench_incremental_1024_kib(b: &mut Bencher) {
     bench_incremental(b, 1024 * KIB);
 }
 
+// Feed the input in windows of MAX_SIMD_DEGREE chunks, as a steady stream
+// would. This should perform about the same as bench_incremental_1024_kib,
+// since each window is a complete subtree that gets the full benefit of SIMD.
+#[bench]
+fn bench_incremental_simd_windows(b: &mut Bencher) {
+    let mut input = RandomInput::new(b, 1024 * KIB);
+    b.iter(|| {
+        let mut hasher = blake3::Hasher::new();
+        for window in input.get().chunks(MAX_SIMD_DEGREE * CHUNK_LEN) {
+            hasher.update(window);
+        }
+        hasher.finalize()
+    });
+}
+
 fn bench_reference(b: &mut Bencher, len: usize) {
     let mut input = RandomInput::new(b, len);
     b.iter(|| {
@@ -497,6 +554,93 @@ fn bench_rayon_1024_kib(b: &mut Bencher) {
     bench_rayon(b, 1024 * KIB);
 }
 
+// Compare depth limits for update_rayon() on a mid-sized input, where task
+// overhead is a noticeable fraction of the work.
+#[cfg(feature = "rayon")]
+fn bench_rayon_depth(b: &mut Bencher, max_depth: usize) {
+    let mut input = RandomInput::new(b, 100 * CHUNK_LEN);
+    b.iter(|| {
+        blake3::Hasher::new()
+            .update_rayon_with_max_depth(input.get(), max_depth)
+            .finalize()
+    });
+}
+
+#[bench]
+#[cfg(feature = "rayon")]
+fn bench_rayon_depth_0(b: &mut Bencher) {
+    bench_rayon_depth(b, 0);
+}
+
+#[bench]
+#[cfg(feature = "rayon")]
+fn bench_rayon_depth_2(b: &mut Bencher) {
+    bench_rayon_depth(b, 2);
+}
+
+#[bench]
+#[cfg(feature = "rayon")]
+fn bench_rayon_depth_4(b: &mut Bencher) {
+    bench_rayon_depth(b, 4);
+}
+
+#[bench]
+#[cfg(feature = "rayon")]
+fn bench_rayon_depth_default(b: &mut Bencher) {
+    let mut input = RandomInput::new(b, 100 * CHUNK_LEN);
+    b.iter(|| blake3::Hasher::new().update_rayon(input.get()).finalize());
+}
+
+#[bench]
+#[cfg(feature = "rayon")]
+fn bench_rayon_depth_unlimited(b: &mut Bencher) {
+    bench_rayon_depth(b, usize::MAX);
+}
+
+// A large input for update_rayon_numa(), big enough to be split across nodes, with
+// update_rayon() on the same input for comparison. On a machine with a single
+// NUMA node, these should be the same.
+#[bench]
+#[cfg(all(feature = "numa", target_os = "linux"))]
+fn bench_rayon_numa_0064_mib(b: &mut Bencher) {
+    let mut input = RandomInput::new(b, 64 * 1024 * KIB);
+    b.iter(|| {
+        blake3::Hasher::new()
+            .update_rayon_numa(input.get())
+            .finalize()
+    });
+}
+
+#[bench]
+#[cfg(all(feature = "numa", target_os = "linux"))]
+fn bench_rayon_numa_baseline_0064_mib(b: &mut Bencher) {
+    bench_rayon(b, 64 * 1024 * KIB);
+}
+
+// Many single-byte updates, as from a caller feeding a parser's output one
+// byte at a time. Compare to bench_incremental_0001_kib and
+// bench_incremental_0016_kib, which hash the same lengths in one update().
+fn bench_single_byte_updates(b: &mut Bencher, len: usize) {
+    let mut input = RandomInput::new(b, len);
+    b.iter(|| {
+        let mut hasher = blake3::Hasher::new();
+        for &byte in input.get() {
+            hasher.update(&[byte]);
+        }
+        hasher.finalize()
+    });
+}
+
+#[bench]
+fn bench_single_byte_updates_0001_kib(b: &mut Bencher) {
+    bench_single_byte_updates(b, 1 * KIB);
+}
+
+#[bench]
+fn bench_single_byte_updates_0016_kib(b: &mut Bencher) {
+    bench_single_byte_updates(b, 16 * KIB);
+}
+
 // This checks that update() splits up its input in increasing powers of 2, so
 // that it can recover a high degree of parallelism when the number of bytes
 // hashed so far is uneven. The performance of this benchmark should be
@@ -0,0 +1,18 @@
+//! Feed arbitrary chunks to `Hasher::update` one at a time, and compare the
+//! result to the reference implementation hashing their concatenation.
+//!
+//! Run with `cargo +nightly fuzz run update_chunks` from the repo root.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|chunks: Vec<Vec<u8>>| {
+    blake3::fuzz_update_chunks(&chunks, |input| {
+        let mut hasher = reference_impl::Hasher::new();
+        hasher.update(input);
+        let mut bytes = [0; blake3::OUT_LEN];
+        hasher.finalize(&mut bytes);
+        bytes.into()
+    });
+});
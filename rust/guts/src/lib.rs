@@ -471,6 +471,272 @@ impl Clone for Implementation {
     }
 }
 
+// 2^54 chunks of 2^10 bytes each is 2^64 bytes, the maximum input length, so the CV stack never
+// holds more than 54 entries.
+const MAX_DEPTH: usize = 54;
+
+// The last compression of a subtree, which can't be performed until we know whether the subtree
+// is the root.
+#[derive(Clone, Copy)]
+struct PendingNode {
+    cv: CVBytes,
+    block: BlockBytes,
+    block_len: u32,
+    counter: u64,
+    flags: u32,
+    // The index of the first chunk in the subtree, which is different from `counter` for parents.
+    first_chunk: u64,
+}
+
+impl PendingNode {
+    fn chaining_value(&self, implementation: &Implementation) -> CVBytes {
+        implementation.compress(
+            &self.block,
+            self.block_len,
+            &self.cv,
+            self.counter,
+            self.flags,
+        )
+    }
+
+    fn root_hash(&self, implementation: &Implementation) -> CVBytes {
+        debug_assert_eq!(self.counter, 0);
+        implementation.compress(&self.block, self.block_len, &self.cv, 0, self.flags | ROOT)
+    }
+}
+
+/// Tree hashing driven by the caller, built on [`Implementation::hash_chunks`] and
+/// [`Implementation::hash_parents`].
+///
+/// The input is pushed in order with [`push_chunks`](TreeHasher::push_chunks). Each push is split
+/// into the largest power-of-2 subtrees that fit, and those are hashed with the full SIMD degree
+/// of the implementation. Every push but the last must be a whole number of chunks.
+/// [`finalize_root`](TreeHasher::finalize_root) merges the subtrees and returns the root hash.
+///
+/// # Example
+///
+/// ```rust
+/// use blake3_guts::{TreeHasher, CHUNK_LEN, DETECTED_IMPL, IV_BYTES};
+///
+/// let input = [0u8; 3 * CHUNK_LEN + 100];
+/// let mut tree_hasher = TreeHasher::new(&DETECTED_IMPL, &IV_BYTES, 0);
+/// tree_hasher.push_chunks(&input[..2 * CHUNK_LEN], 0);
+/// tree_hasher.push_chunks(&input[2 * CHUNK_LEN..], 2);
+/// let hash = tree_hasher.finalize_root();
+///
+/// let mut reference_hasher = reference_impl::Hasher::new();
+/// reference_hasher.update(&input);
+/// let mut expected_hash = [0u8; 32];
+/// reference_hasher.finalize(&mut expected_hash);
+///
+/// assert_eq!(hash, expected_hash);
+/// ```
+#[derive(Clone)]
+pub struct TreeHasher<'a> {
+    implementation: &'a Implementation,
+    key: CVBytes,
+    flags: u32,
+    cv_stack: [CVBytes; MAX_DEPTH],
+    cv_stack_len: usize,
+    pending: Option<PendingNode>,
+    // Including the pending subtree.
+    chunk_count: u64,
+    partial_chunk: bool,
+}
+
+impl<'a> TreeHasher<'a> {
+    /// Create a `TreeHasher` with nothing pushed yet. `key` and `flags` are the same as for
+    /// [`Implementation::hash_chunks`]: [`IV_BYTES`] and 0 for the regular hash function, or the
+    /// key or context key and the matching mode flag for the other modes.
+    pub fn new(implementation: &'a Implementation, key: &CVBytes, flags: u32) -> Self {
+        Self {
+            implementation,
+            key: *key,
+            flags,
+            cv_stack: [[0; 32]; MAX_DEPTH],
+            cv_stack_len: 0,
+            pending: None,
+            chunk_count: 0,
+            partial_chunk: false,
+        }
+    }
+
+    /// Hash the next span of input. `counter` is the index of the span's first chunk, which must
+    /// equal the number of chunks pushed so far. Every span but the last must be a whole number
+    /// of chunks. Pushing an empty span does nothing.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `counter` is out of order, or if a previous span ended with a partial chunk.
+    pub fn push_chunks(&mut self, mut input: &[u8], counter: u64) {
+        if input.is_empty() {
+            return;
+        }
+        assert_eq!(counter, self.chunk_count, "spans must be pushed in order");
+        assert!(
+            !self.partial_chunk,
+            "only the last span can end with a partial chunk"
+        );
+        while !input.is_empty() {
+            // As in blake3::Hasher::update, the subtree has to be a power-of-2 number of chunks,
+            // and it has to evenly divide the number of chunks so far.
+            let mut subtree_len = cmp::min(input.len(), CHUNK_LEN);
+            if input.len() > CHUNK_LEN {
+                subtree_len = largest_power_of_two_leq(input.len());
+                while (subtree_len / CHUNK_LEN - 1) as u64 & self.chunk_count != 0 {
+                    subtree_len /= 2;
+                }
+            }
+            if let Some(pending) = self.pending.take() {
+                let cv = pending.chaining_value(self.implementation);
+                self.push_cv(&cv, pending.first_chunk);
+            }
+            let subtree = &input[..subtree_len];
+            self.pending = Some(if subtree_len <= CHUNK_LEN {
+                self.chunk_node(subtree, self.chunk_count)
+            } else {
+                self.parent_node(subtree, self.chunk_count)
+            });
+            // usize::div_ceil needs Rust 1.73, which is newer than our MSRV.
+            #[allow(clippy::manual_div_ceil)]
+            let subtree_chunks = (subtree_len + CHUNK_LEN - 1) / CHUNK_LEN;
+            self.chunk_count += subtree_chunks as u64;
+            self.partial_chunk = subtree_len & (CHUNK_LEN - 1) != 0;
+            input = &input[subtree_len..];
+        }
+    }
+
+    /// Merge everything pushed so far and return the root hash. If nothing has been pushed, this
+    /// is the hash of the empty input.
+    pub fn finalize_root(&self) -> CVBytes {
+        let pending = match self.pending {
+            Some(pending) => pending,
+            None => self.chunk_node(&[], 0),
+        };
+        let mut merged = self.clone();
+        merged.merge_cv_stack(pending.first_chunk);
+        if merged.cv_stack_len == 0 {
+            return pending.root_hash(self.implementation);
+        }
+        let mut right_cv = pending.chaining_value(self.implementation);
+        let mut index = merged.cv_stack_len;
+        loop {
+            index -= 1;
+            let parent = PendingNode {
+                cv: self.key,
+                block: parent_block(&merged.cv_stack[index], &right_cv),
+                block_len: BLOCK_LEN as u32,
+                counter: 0,
+                flags: self.flags | PARENT,
+                first_chunk: 0,
+            };
+            if index == 0 {
+                return parent.root_hash(self.implementation);
+            }
+            right_cv = parent.chaining_value(self.implementation);
+        }
+    }
+
+    fn chunk_node(&self, mut input: &[u8], counter: u64) -> PendingNode {
+        debug_assert!(input.len() <= CHUNK_LEN);
+        let mut cv = self.key;
+        let mut flags = self.flags | CHUNK_START;
+        while input.len() > BLOCK_LEN {
+            cv = self.implementation.compress(
+                input[..BLOCK_LEN].try_into().unwrap(),
+                BLOCK_LEN as u32,
+                &cv,
+                counter,
+                flags,
+            );
+            input = &input[BLOCK_LEN..];
+            flags &= !CHUNK_START;
+        }
+        let mut block = [0u8; BLOCK_LEN];
+        block[..input.len()].copy_from_slice(input);
+        PendingNode {
+            cv,
+            block,
+            block_len: input.len() as u32,
+            counter,
+            flags: flags | CHUNK_END,
+            first_chunk: counter,
+        }
+    }
+
+    fn parent_node(&self, input: &[u8], counter: u64) -> PendingNode {
+        debug_assert!(input.len() > CHUNK_LEN);
+        let mut cvs = TransposedVectors::new();
+        // The right half of these vectors is never used.
+        let (cvs_left, _) = self.implementation.split_transposed_vectors(&mut cvs);
+        let mut num_cvs = self.hash_subtree_wide(input, counter, cvs_left);
+        while num_cvs > 2 {
+            num_cvs = self
+                .implementation
+                .reduce_parents(&mut cvs, num_cvs, &self.key, self.flags);
+        }
+        PendingNode {
+            cv: self.key,
+            block: cvs.extract_parent_node(0),
+            block_len: BLOCK_LEN as u32,
+            counter: 0,
+            flags: self.flags | PARENT,
+            first_chunk: counter,
+        }
+    }
+
+    // Hash a subtree of more than one chunk into at least two CVs, splitting it recursively until
+    // each part fits in a single call to hash_chunks.
+    fn hash_subtree_wide(&self, input: &[u8], counter: u64, output: TransposedSplit) -> usize {
+        let degree = self.implementation.degree();
+        if input.len() <= degree * CHUNK_LEN {
+            return self
+                .implementation
+                .hash_chunks(input, &self.key, counter, self.flags, output);
+        }
+        let (left_input, right_input) = input.split_at(left_len(input.len()));
+        let right_counter = counter + (left_input.len() / CHUNK_LEN) as u64;
+        let mut child_output = TransposedVectors::new();
+        let (left_output, right_output) = self
+            .implementation
+            .split_transposed_vectors(&mut child_output);
+        let mut children = self.hash_subtree_wide(left_input, counter, left_output);
+        debug_assert_eq!(children, degree);
+        children += self.hash_subtree_wide(right_input, right_counter, right_output);
+        self.implementation
+            .hash_parents(&child_output, children, &self.key, self.flags, output)
+    }
+
+    fn merge_cv_stack(&mut self, total_chunks: u64) {
+        let post_merge_stack_len = total_chunks.count_ones() as usize;
+        while self.cv_stack_len > post_merge_stack_len {
+            let right_child = self.cv_stack[self.cv_stack_len - 1];
+            let left_child = self.cv_stack[self.cv_stack_len - 2];
+            self.cv_stack[self.cv_stack_len - 2] = self.implementation.compress(
+                &parent_block(&left_child, &right_child),
+                BLOCK_LEN as u32,
+                &self.key,
+                0,
+                self.flags | PARENT,
+            );
+            self.cv_stack_len -= 1;
+        }
+    }
+
+    fn push_cv(&mut self, cv: &CVBytes, first_chunk: u64) {
+        self.merge_cv_stack(first_chunk);
+        self.cv_stack[self.cv_stack_len] = *cv;
+        self.cv_stack_len += 1;
+    }
+}
+
+fn parent_block(left_child: &CVBytes, right_child: &CVBytes) -> BlockBytes {
+    let mut block = [0u8; BLOCK_LEN];
+    block[..32].copy_from_slice(left_child);
+    block[32..].copy_from_slice(right_child);
+    block
+}
+
 // never less than 2
 type DegreeFn = unsafe extern "C" fn() -> usize;
 
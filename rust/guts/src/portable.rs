@@ -238,6 +238,11 @@ mod test {
         crate::test::test_chunks_and_parents_vs_reference(&implementation());
     }
 
+    #[test]
+    fn test_tree_hasher_vs_reference() {
+        crate::test::test_tree_hasher_vs_reference(&implementation());
+    }
+
     // This is circular but do it anyway.
     #[test]
     fn test_xof_vs_portable() {
@@ -316,6 +316,78 @@ pub fn test_chunks_and_parents_vs_reference(test_impl: &Implementation) {
     }
 }
 
+pub fn test_tree_hasher_vs_reference(test_impl: &Implementation) {
+    const MAX_INPUT_LEN: usize = 2 * MAX_SIMD_DEGREE * CHUNK_LEN + 3 * CHUNK_LEN;
+    let mut input_buf = [0u8; MAX_INPUT_LEN + 95];
+    paint_test_input(&mut input_buf);
+    // Unlike root_hash_with_chunks_and_parents, TreeHasher handles the 1-chunk-or-less case.
+    let mut test_lengths = vec![0, 1, BLOCK_LEN, CHUNK_LEN - 1, CHUNK_LEN, CHUNK_LEN + 1];
+    let mut next_len = 2 * CHUNK_LEN;
+    while next_len <= MAX_INPUT_LEN {
+        test_lengths.push(next_len - 95);
+        test_lengths.push(next_len);
+        test_lengths.push(next_len + 95);
+        next_len += CHUNK_LEN;
+    }
+    for test_len in test_lengths {
+        dbg!(test_len);
+        let input = &input_buf[..test_len];
+
+        let mut ref_hasher = reference_impl::Hasher::new_keyed(&TEST_KEY);
+        ref_hasher.update(input);
+        let mut ref_hash = [0u8; 32];
+        ref_hasher.finalize(&mut ref_hash);
+
+        // Push the whole input at once.
+        let mut tree_hasher = TreeHasher::new(test_impl, &TEST_KEY, KEYED_HASH);
+        tree_hasher.push_chunks(input, 0);
+        assert_eq!(ref_hash, tree_hasher.finalize_root());
+
+        // Push the whole chunks one at a time, and then the remainder.
+        let mut tree_hasher = TreeHasher::new(test_impl, &TEST_KEY, KEYED_HASH);
+        let mut chunks = input.chunks(CHUNK_LEN);
+        for (counter, chunk) in chunks.by_ref().enumerate() {
+            tree_hasher.push_chunks(chunk, counter as u64);
+        }
+        assert_eq!(ref_hash, tree_hasher.finalize_root());
+
+        // Push an odd number of chunks first, to force the second push to split its subtrees.
+        let mut tree_hasher = TreeHasher::new(test_impl, &TEST_KEY, KEYED_HASH);
+        let split = cmp::min(3 * CHUNK_LEN, input.len() / CHUNK_LEN * CHUNK_LEN);
+        tree_hasher.push_chunks(&input[..split], 0);
+        tree_hasher.push_chunks(&input[split..], (split / CHUNK_LEN) as u64);
+        assert_eq!(ref_hash, tree_hasher.finalize_root());
+
+        // The unkeyed result should match the chunks-and-parents test harness.
+        if input.len() > CHUNK_LEN {
+            let mut tree_hasher = TreeHasher::new(test_impl, &IV_BYTES, 0);
+            tree_hasher.push_chunks(input, 0);
+            assert_eq!(
+                root_hash_with_chunks_and_parents(test_impl, input),
+                tree_hasher.finalize_root(),
+            );
+        }
+    }
+}
+
+#[test]
+#[should_panic]
+fn test_tree_hasher_out_of_order() {
+    let input = [0u8; 2 * CHUNK_LEN];
+    let mut tree_hasher = TreeHasher::new(&DETECTED_IMPL, &IV_BYTES, 0);
+    tree_hasher.push_chunks(&input[..CHUNK_LEN], 0);
+    tree_hasher.push_chunks(&input[CHUNK_LEN..], 0);
+}
+
+#[test]
+#[should_panic]
+fn test_tree_hasher_push_after_partial_chunk() {
+    let input = [0u8; 2 * CHUNK_LEN];
+    let mut tree_hasher = TreeHasher::new(&DETECTED_IMPL, &IV_BYTES, 0);
+    tree_hasher.push_chunks(&input[..100], 0);
+    tree_hasher.push_chunks(&input[100..], 1);
+}
+
 pub fn test_xof_vs_portable(test_impl: &Implementation) {
     let flags = CHUNK_START | CHUNK_END | KEYED_HASH;
     for counter in INITIAL_COUNTERS {
@@ -0,0 +1,80 @@
+//! Backends chosen at compile time, for [`hash_with`](crate::hash_with).
+//!
+//! Each backend is an uninhabited type that implements [`PlatformTrait`]. The SIMD backends only
+//! exist when the corresponding instruction set is enabled for the whole build (for example with
+//! `-C target-feature=+avx2` or `-C target-cpu=native`), so naming one is always safe, and a
+//! build that names one it doesn't enable fails to compile. [`Portable`] is always available.
+
+use crate::platform::Platform;
+
+/// A backend chosen at compile time. See the [module docs](self).
+///
+/// This trait is sealed, and it can't be implemented outside this crate.
+pub trait PlatformTrait: sealed::Sealed {
+    #[doc(hidden)]
+    const PLATFORM: Platform;
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+macro_rules! platform_type {
+    ($(#[$attr:meta])* $name:ident) => {
+        $(#[$attr])*
+        pub enum $name {}
+
+        $(#[$attr])*
+        impl sealed::Sealed for $name {}
+
+        $(#[$attr])*
+        impl PlatformTrait for $name {
+            const PLATFORM: Platform = Platform::$name;
+        }
+    };
+}
+
+platform_type!(
+    /// The portable implementation, which works on every target.
+    Portable
+);
+platform_type!(
+    /// SSE2, on x86 and x86_64 builds with the `sse2` target feature.
+    #[cfg(all(
+        any(target_arch = "x86", target_arch = "x86_64"),
+        target_feature = "sse2"
+    ))]
+    SSE2
+);
+platform_type!(
+    /// SSE4.1, on x86 and x86_64 builds with the `sse4.1` target feature.
+    #[cfg(all(
+        any(target_arch = "x86", target_arch = "x86_64"),
+        target_feature = "sse4.1"
+    ))]
+    SSE41
+);
+platform_type!(
+    /// AVX2, on x86 and x86_64 builds with the `avx2` target feature.
+    #[cfg(all(
+        any(target_arch = "x86", target_arch = "x86_64"),
+        target_feature = "avx2"
+    ))]
+    AVX2
+);
+platform_type!(
+    /// AVX-512, on x86 and x86_64 builds with the `avx512f` and `avx512vl` target features,
+    /// when the AVX-512 implementation is built.
+    #[cfg(all(
+        blake3_avx512_ffi,
+        any(target_arch = "x86", target_arch = "x86_64"),
+        target_feature = "avx512f",
+        target_feature = "avx512vl"
+    ))]
+    AVX512
+);
+platform_type!(
+    /// NEON, on builds where the NEON implementation is enabled. See the `neon` Cargo feature.
+    #[cfg(blake3_neon)]
+    NEON
+);
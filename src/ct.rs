@@ -0,0 +1,81 @@
+//! Constant-time comparisons.
+//!
+//! The `PartialEq` implementations for [`Hash`] are already constant-time, so `==` is safe for
+//! comparing MACs. This module names that operation explicitly, for code where the security
+//! boundary should be obvious to a reader, and it's where the crate's constant-time primitives
+//! live.
+
+use crate::{Hash, OUT_LEN};
+use core::fmt;
+
+/// Compare two hashes in constant time. This is the same as `a == b`.
+#[inline]
+pub fn eq(a: &Hash, b: &Hash) -> bool {
+    eq_32(a.as_bytes(), b.as_bytes())
+}
+
+/// Compare a computed MAC to an expected one in constant time, and return an error if they
+/// differ.
+///
+/// # Example
+///
+/// ```
+/// let key = [42; 32];
+/// let tag = blake3::keyed_hash(&key, b"message");
+/// // Later, check a tag received along with the message.
+/// blake3::ct::verify_tag(&blake3::keyed_hash(&key, b"message"), &tag)?;
+/// assert!(blake3::ct::verify_tag(&blake3::keyed_hash(&key, b"forged"), &tag).is_err());
+/// # Ok::<(), blake3::ct::MacError>(())
+/// ```
+pub fn verify_tag(computed: &Hash, expected: &Hash) -> Result<(), MacError> {
+    if eq(computed, expected) {
+        Ok(())
+    } else {
+        Err(MacError)
+    }
+}
+
+/// The error type for [`verify_tag`], returned when two MACs don't match.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MacError;
+
+impl fmt::Display for MacError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("MAC verification failed")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MacError {}
+
+#[inline]
+pub(crate) fn eq_32(a: &[u8; OUT_LEN], b: &[u8; OUT_LEN]) -> bool {
+    #[cfg(miri)]
+    return eq_miri(a, b);
+    #[cfg(not(miri))]
+    constant_time_eq::constant_time_eq_32(a, b)
+}
+
+// Constant-time if the slices have the same length. Different lengths return false immediately.
+#[inline]
+pub(crate) fn eq_slices(a: &[u8], b: &[u8]) -> bool {
+    #[cfg(miri)]
+    return eq_miri(a, b);
+    #[cfg(not(miri))]
+    constant_time_eq::constant_time_eq(a, b)
+}
+
+// A proper implementation of constant time equality is tricky, and we get it from the
+// constant_time_eq crate instead of rolling our own. However, that crate isn't compatible with
+// Miri, so we roll our own just for that.
+#[cfg(miri)]
+fn eq_miri(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut x = 0;
+    for i in 0..a.len() {
+        x |= a[i] ^ b[i];
+    }
+    x == 0
+}
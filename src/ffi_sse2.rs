@@ -32,6 +32,7 @@ pub unsafe fn compress_xof(
 }
 
 // Unsafe because this may only be called on platforms supporting SSE2.
+#[allow(clippy::too_many_arguments)]
 pub unsafe fn hash_many<const N: usize>(
     inputs: &[&[u8; N]],
     key: &CVWords,
@@ -6,10 +6,10 @@
 //! We could stabilize something like this module in the future. If you have a
 //! use case for it, please let us know by filing a GitHub issue.
 
-pub const BLOCK_LEN: usize = 64;
-pub const CHUNK_LEN: usize = 1024;
+pub use crate::{BLOCK_LEN, CHUNK_LEN};
 
 #[derive(Clone, Debug)]
+#[allow(clippy::len_without_is_empty)]
 pub struct ChunkState(crate::ChunkState);
 
 impl ChunkState {
@@ -66,6 +66,112 @@ pub fn parent_cv(
     }
 }
 
+// Combine the chaining values of adjacent subtrees into the root hash, for
+// callers that hash different parts of the input independently. Each element
+// of `subtrees` is a non-root CV and the number of chunks it covers, in order
+// from the start of the input. Every subtree must be a complete, aligned
+// subtree of the BLAKE3 tree: a power-of-two number of chunks, starting at a
+// multiple of that number. The last subtree may also be the incomplete right
+// edge of the tree, which is aligned to its size rounded up to a power of two.
+// As with parent_cv above, this assumes the regular hash mode.
+//
+// Panics if there are fewer than two subtrees (the root of a single subtree
+// isn't a parent node, and its hash needs the whole chunk or parent block), or
+// if any subtree is empty or misaligned, or if the subtrees add up to more than
+// 2^54 chunks (2^64 bytes).
+pub fn combine_subtrees(subtrees: &[(crate::Hash, u64)]) -> crate::Hash {
+    assert!(subtrees.len() >= 2, "at least two subtrees are required");
+    // Like the Hasher's CV stack, this merges lazily.
+    let mut cv_stack: arrayvec::ArrayVec<crate::Hash, { crate::MAX_DEPTH + 1 }> =
+        arrayvec::ArrayVec::new();
+    let mut total_chunks: u64 = 0;
+    for (i, &(cv, num_chunks)) in subtrees.iter().enumerate() {
+        assert!(num_chunks > 0, "empty subtree");
+        let span = num_chunks
+            .checked_next_power_of_two()
+            .expect("subtree too large");
+        assert!(
+            num_chunks == span || i == subtrees.len() - 1,
+            "only the last subtree can be incomplete"
+        );
+        assert!(total_chunks & (span - 1) == 0, "misaligned subtree");
+        while cv_stack.len() > total_chunks.count_ones() as usize {
+            let right_child = cv_stack.pop().unwrap();
+            let left_child = cv_stack.pop().unwrap();
+            cv_stack.push(parent_cv(&left_child, &right_child, false));
+        }
+        cv_stack.push(cv);
+        total_chunks = total_chunks
+            .checked_add(num_chunks)
+            .expect("too many chunks");
+        // 2^54 chunks is 2^64 bytes, the most the CV stack has room for.
+        assert!(total_chunks <= 1 << crate::MAX_DEPTH, "too many chunks");
+    }
+    // Merge the rest of the stack from right to left, and finalize the last
+    // parent as the root.
+    let mut right_child = cv_stack.pop().unwrap();
+    while let Some(left_child) = cv_stack.pop() {
+        right_child = parent_cv(&left_child, &right_child, cv_stack.is_empty());
+    }
+    right_child
+}
+
+// The compression function, using the platform detected at runtime. This is for
+// benchmarking the compression function in isolation. Use
+// crate::platform::Platform directly to compare specific backends.
+pub fn compress_in_place_detected(
+    cv: &mut [u32; 8],
+    block: &[u8; BLOCK_LEN],
+    block_len: u8,
+    counter: u64,
+    flags: u8,
+) {
+    crate::platform::Platform::detect().compress_in_place(cv, block, block_len, counter, flags);
+}
+
+// The batched compression function behind chunk and parent hashing, using the
+// platform detected at runtime. Each of the `inputs` is compressed as a
+// sequence of N/64 blocks, and its 32-byte chaining value is written to the
+// corresponding position in `out`, so the inputs are independent "rows" even
+// though SIMD implementations process them as transposed "columns", one input
+// per vector lane. The first block of each input adds `flags_start`, and the
+// last adds `flags_end`. If `increment_counter` is Yes, input i uses the
+// counter `counter + i` (as for chunks); otherwise they all use `counter` (as
+// for parents).
+//
+// Panics if N isn't a nonzero multiple of BLOCK_LEN, or if `out` is shorter
+// than 32 bytes per input.
+#[allow(clippy::too_many_arguments)]
+pub fn hash_many<const N: usize>(
+    inputs: &[&[u8; N]],
+    key: &[u32; 8],
+    counter: u64,
+    increment_counter: crate::IncrementCounter,
+    flags: u8,
+    flags_start: u8,
+    flags_end: u8,
+    out: &mut [u8],
+) {
+    assert!(
+        N > 0 && N & (BLOCK_LEN - 1) == 0,
+        "input length must be whole blocks"
+    );
+    assert!(
+        out.len() >= inputs.len() * crate::OUT_LEN,
+        "output too short"
+    );
+    crate::platform::Platform::detect().hash_many(
+        inputs,
+        key,
+        counter,
+        increment_counter,
+        flags,
+        flags_start,
+        flags_end,
+        out,
+    );
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -78,16 +184,99 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_compress_in_place_detected() {
+        crate::test::test_compress_fn(compress_in_place_detected, crate::portable::compress_xof);
+    }
+
+    #[test]
+    fn test_hash_many() {
+        crate::test::test_hash_many_fn(hash_many, hash_many);
+    }
+
+    // The non-root CV of a subtree, from ChunkState and parent_cv.
+    fn subtree_cv(input: &[u8], chunk_counter: u64) -> crate::Hash {
+        if input.len() <= CHUNK_LEN {
+            return ChunkState::new(chunk_counter).update(input).finalize(false);
+        }
+        let left_len = crate::left_len(input.len());
+        let left = subtree_cv(&input[..left_len], chunk_counter);
+        let right_counter = chunk_counter + (left_len / CHUNK_LEN) as u64;
+        let right = subtree_cv(&input[left_len..], right_counter);
+        parent_cv(&left, &right, false)
+    }
+
+    #[test]
+    fn test_combine_subtrees() {
+        let mut input = [0; 16 * CHUNK_LEN + 100];
+        crate::test::paint_test_input(&mut input);
+        // Each case is a list of subtree sizes in chunks. The last subtree
+        // takes whatever input is left, which may end with a partial chunk.
+        let cases: &[(usize, &[usize])] = &[
+            (16 * CHUNK_LEN, &[4, 4, 4, 4]),
+            (16 * CHUNK_LEN + 100, &[8, 4, 2, 2, 1]),
+            (16 * CHUNK_LEN + 100, &[8, 8, 1]),
+            (15 * CHUNK_LEN + 1, &[8, 4, 2, 2]),
+            (7 * CHUNK_LEN, &[4, 3]),
+            (2 * CHUNK_LEN, &[1, 1]),
+            (CHUNK_LEN + 1, &[1, 1]),
+        ];
+        for &(len, sizes) in cases {
+            let mut subtrees = arrayvec::ArrayVec::<_, 8>::new();
+            let mut chunk_counter = 0;
+            for (i, &num_chunks) in sizes.iter().enumerate() {
+                let start = chunk_counter * CHUNK_LEN;
+                let end = if i == sizes.len() - 1 {
+                    len
+                } else {
+                    start + num_chunks * CHUNK_LEN
+                };
+                let cv = subtree_cv(&input[start..end], chunk_counter as u64);
+                subtrees.push((cv, num_chunks as u64));
+                chunk_counter += num_chunks;
+            }
+            assert_eq!(
+                combine_subtrees(&subtrees),
+                crate::hash(&input[..len]),
+                "len {} sizes {:?}",
+                len,
+                sizes,
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "misaligned subtree")]
+    fn test_combine_subtrees_misaligned() {
+        let cv = crate::Hash::from_bytes([0; 32]);
+        // A 4-chunk subtree can't start at chunk 2.
+        combine_subtrees(&[(cv, 2), (cv, 4)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "only the last subtree can be incomplete")]
+    fn test_combine_subtrees_incomplete() {
+        let cv = crate::Hash::from_bytes([0; 32]);
+        combine_subtrees(&[(cv, 3), (cv, 1)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "too many chunks")]
+    fn test_combine_subtrees_too_many_chunks() {
+        let cv = crate::Hash::from_bytes([0; 32]);
+        combine_subtrees(&[(cv, 1 << crate::MAX_DEPTH), (cv, 1 << crate::MAX_DEPTH)]);
+    }
+
     #[test]
     fn test_parents() {
         let mut hasher = crate::Hasher::new();
         let mut buf = [0; crate::CHUNK_LEN];
 
-        buf[0] = 'a' as u8;
+        buf[0] = b'a';
         hasher.update(&buf);
         let chunk0_cv = ChunkState::new(0).update(&buf).finalize(false);
 
-        buf[0] = 'b' as u8;
+        buf[0] = b'b';
         hasher.update(&buf);
         let chunk1_cv = ChunkState::new(1).update(&buf).finalize(false);
 
@@ -2,20 +2,103 @@
 
 #[cfg(feature = "std")]
 pub(crate) fn copy_wide(
+    reader: impl std::io::Read,
+    hasher: &mut crate::Hasher,
+) -> std::io::Result<u64> {
+    copy_with_buf(reader, hasher, &mut [0; 65536])
+}
+
+#[cfg(feature = "std")]
+pub(crate) fn copy_with_buf(
+    mut reader: impl std::io::Read,
+    hasher: &mut crate::Hasher,
+    buffer: &mut [u8],
+) -> std::io::Result<u64> {
+    // An empty buffer would look like EOF.
+    assert!(!buffer.is_empty(), "empty read buffer");
+    let mut total = 0;
+    loop {
+        match reader.read(buffer) {
+            Ok(0) => return Ok(total),
+            Ok(n) => {
+                hasher.update(&buffer[..n]);
+                total += n as u64;
+            }
+            // see test_update_reader_interrupted
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// Copy exactly `len` bytes, returning an error if the reader ends early or has more bytes after
+// that. Used by Hasher::update_file_with_len() and hash_dir(), which commit to the length before
+// the contents.
+#[cfg(feature = "std")]
+pub(crate) fn copy_exact(
+    reader: impl std::io::Read,
+    hasher: &mut crate::Hasher,
+    len: u64,
+) -> std::io::Result<()> {
+    let mut take = reader.take(len);
+    if copy_wide(&mut take, hasher)? != len {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            "file shrank while hashing",
+        ));
+    }
+    let mut reader = take.into_inner();
+    loop {
+        match reader.read(&mut [0]) {
+            Ok(0) => return Ok(()),
+            Ok(_) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "file grew while hashing",
+                ))
+            }
+            // see test_update_reader_interrupted
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// Like copy_with_buf, but give up with TimedOut once `deadline` has passed. Everything read before
+// that has already gone to the hasher. WouldBlock and TimedOut from the reader are retried until
+// the deadline, since readers with a read timeout (like a TcpStream) return those when a single
+// read times out.
+#[cfg(feature = "std")]
+pub(crate) fn copy_until(
     mut reader: impl std::io::Read,
     hasher: &mut crate::Hasher,
+    deadline: std::time::Instant,
 ) -> std::io::Result<u64> {
     let mut buffer = [0; 65536];
     let mut total = 0;
     loop {
+        let now = std::time::Instant::now();
+        if now >= deadline {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!("deadline passed after reading {} bytes", total),
+            ));
+        }
         match reader.read(&mut buffer) {
             Ok(0) => return Ok(total),
             Ok(n) => {
                 hasher.update(&buffer[..n]);
                 total += n as u64;
             }
-            // see test_update_reader_interrupted
             Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e)
+                if e.kind() == std::io::ErrorKind::WouldBlock
+                    || e.kind() == std::io::ErrorKind::TimedOut =>
+            {
+                // Don't spin on a non-blocking reader.
+                let pause = std::time::Duration::from_millis(1);
+                std::thread::sleep(pause.min(deadline.saturating_duration_since(now)));
+            }
             Err(e) => return Err(e),
         }
     }
@@ -54,7 +137,7 @@ pub(crate) fn maybe_mmap_file(file: &std::fs::File) -> std::io::Result<Option<me
     if !metadata.is_file() {
         // Not a real file.
         Ok(None)
-    } else if file_size > isize::max_value() as u64 {
+    } else if file_size > isize::MAX as u64 {
         // Too long to safely map.
         // https://github.com/danburkert/memmap-rs/issues/69
         Ok(None)
@@ -77,3 +160,43 @@ pub(crate) fn maybe_mmap_file(file: &std::fs::File) -> std::io::Result<Option<me
         Ok(Some(map))
     }
 }
+
+// All the regular files under `root`, as pairs of a relative path with `/` separators and a full
+// path, sorted by the relative path. Used by hash_dir().
+#[cfg(feature = "std")]
+pub(crate) fn sorted_files(
+    root: &std::path::Path,
+) -> std::io::Result<Vec<(String, std::path::PathBuf)>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![(String::new(), root.to_path_buf())];
+    while let Some((dir_relative, dir_full)) = dirs.pop() {
+        for entry in std::fs::read_dir(&dir_full)? {
+            let entry = entry?;
+            let name = entry.file_name().into_string().map_err(|name| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("path is not valid UTF-8: {:?}", dir_full.join(name)),
+                )
+            })?;
+            let relative = if dir_relative.is_empty() {
+                name
+            } else {
+                format!("{}/{}", dir_relative, name)
+            };
+            // file_type() doesn't follow symlinks.
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                dirs.push((relative, entry.path()));
+            } else if file_type.is_file() {
+                files.push((relative, entry.path()));
+            } else {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("not a regular file or directory: {:?}", entry.path()),
+                ));
+            }
+        }
+    }
+    files.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+    Ok(files)
+}
@@ -71,6 +71,15 @@ impl Join for RayonJoin {
     }
 }
 
+/// The default depth limit for `Hasher::update_rayon`. Splitting stops once each thread in the
+/// current Rayon pool has about four subtrees to work on, which leaves work stealing some room to
+/// balance uneven progress without flooding the pool with tiny tasks.
+#[cfg(feature = "rayon")]
+pub fn default_max_depth() -> usize {
+    let threads = rayon::current_num_threads();
+    threads.next_power_of_two().trailing_zeros() as usize + 2
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -89,4 +98,14 @@ mod test {
         let oper_b = || 2 + 2;
         assert_eq!((2, 4), RayonJoin::join(oper_a, oper_b));
     }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn test_default_max_depth() {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(6)
+            .build()
+            .unwrap();
+        assert_eq!(pool.install(default_max_depth), 5);
+    }
 }
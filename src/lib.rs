@@ -34,11 +34,12 @@
 //!
 //! The `std` feature (the only feature enabled by default) is required for
 //! implementations of the [`Write`] and [`Seek`] traits, the
-//! [`update_reader`](Hasher::update_reader) helper method, and runtime CPU
-//! feature detection on x86. If this feature is disabled, the only way to use
-//! the x86 SIMD implementations is to enable the corresponding instruction sets
-//! globally, with e.g. `RUSTFLAGS="-C target-cpu=native"`. The resulting binary
-//! will not be portable to other machines.
+//! [`update_reader`](Hasher::update_reader) helper method, the [`HasherPool`]
+//! type, and runtime CPU feature detection on x86. If this feature is
+//! disabled, the only way to use the x86 SIMD implementations is to enable
+//! the corresponding instruction sets globally, with e.g.
+//! `RUSTFLAGS="-C target-cpu=native"`. The resulting binary will not be
+//! portable to other machines.
 //!
 //! The `rayon` feature (disabled by default, but enabled for [docs.rs]) adds
 //! the [`update_rayon`](Hasher::update_rayon) and (in combination with `mmap`
@@ -51,6 +52,21 @@
 //! [`update_mmap_rayon`](Hasher::update_mmap_rayon) helper methods for
 //! memory-mapped IO.
 //!
+//! The `rand` feature (disabled by default, but enabled for [docs.rs]) adds
+//! [`Hash::random`], for generating random hashes with any
+//! [`RngCore`](https://docs.rs/rand_core/0.6/rand_core/trait.RngCore.html).
+//!
+//! The `bytes` feature (disabled by default, but enabled for [docs.rs]) adds
+//! [`update_buf`](Hasher::update_buf), for hashing any
+//! [`bytes::Buf`](https://docs.rs/bytes/1/bytes/trait.Buf.html).
+//!
+//! The `numa` feature (disabled by default, Linux only) adds
+//! [`update_rayon_numa`](Hasher::update_rayon_numa), a variant of
+//! `update_rayon` for machines with more than one NUMA node.
+//!
+//! The `test-util` feature (disabled by default, but enabled for [docs.rs])
+//! adds the [`assert_hash_eq!`] macro, for tests that compare hashes.
+//!
 //! The `zeroize` feature (disabled by default, but enabled for [docs.rs])
 //! implements
 //! [`Zeroize`](https://docs.rs/zeroize/latest/zeroize/trait.Zeroize.html) for
@@ -74,6 +90,13 @@
 //! expect breaking changes between patch versions. (The "-preview" feature name
 //! follows the conventions of the RustCrypto [`signature`] crate.)
 //!
+//! There's no feature for removing the keyed, key derivation, or extended
+//! output modes in size-constrained builds. Cargo features need to be
+//! additive, and a feature that removed APIs would break any other crate in
+//! the same build that uses them. It also wouldn't save much. All of those
+//! modes share the same compression function as [`hash`], and the linker drops
+//! whatever your program doesn't call.
+//!
 //! [`Hasher::update_rayon`]: struct.Hasher.html#method.update_rayon
 //! [BLAKE3]: https://blake3.io
 //! [Rayon]: https://github.com/rayon-rs/rayon
@@ -130,10 +153,25 @@ mod sse41;
 #[cfg(feature = "traits-preview")]
 pub mod traits;
 
+pub mod backend;
+pub mod ct;
+
 mod io;
 mod join;
+#[cfg(all(feature = "numa", target_os = "linux"))]
+mod numa;
+#[cfg(feature = "std")]
+mod pool;
+mod short_hash;
+#[cfg(feature = "test-util")]
+#[doc(hidden)]
+pub mod test_util;
 
-use arrayref::{array_mut_ref, array_ref};
+#[cfg(feature = "std")]
+pub use pool::{HasherPool, PooledHasher};
+pub use short_hash::ShortHash;
+
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
 use arrayvec::{ArrayString, ArrayVec};
 use core::cmp;
 use core::fmt;
@@ -143,10 +181,32 @@ use platform::{Platform, MAX_SIMD_DEGREE, MAX_SIMD_DEGREE_OR_2};
 pub const OUT_LEN: usize = 32;
 
 /// The number of bytes in a key, 32.
+///
+/// # Example
+///
+/// ```
+/// let mut key = [0; blake3::KEY_LEN];
+/// key[..5].copy_from_slice(b"hello");
+/// let mac = blake3::keyed_hash(&key, b"message");
+/// # let _ = mac;
+/// ```
 pub const KEY_LEN: usize = 32;
 
+/// The number of bytes in a block, 64.
+///
+/// You don't usually need to think about this number. One case where it matters is calling
+/// [`OutputReader::fill`] in a loop, where using a `buf` argument that's a multiple of
+/// `BLOCK_LEN` avoids repeating work.
+pub const BLOCK_LEN: usize = 64;
+
+/// The number of bytes in a chunk, 1024.
+///
+/// You don't usually need to think about this number either, but it often comes up in
+/// benchmarks, because the maximum degree of parallelism used by the implementation equals the
+/// number of chunks.
+pub const CHUNK_LEN: usize = 1024;
+
 const MAX_DEPTH: usize = 54; // 2^54 * CHUNK_LEN = 2^64
-use guts::{BLOCK_LEN, CHUNK_LEN};
 
 // While iterating the compression function within a chunk, the CV is
 // represented as words, to avoid doing two extra endianness conversions for
@@ -218,10 +278,19 @@ fn counter_high(counter: u64) -> u32 {
 /// [`FromStr`]: https://doc.rust-lang.org/std/str/trait.FromStr.html
 #[cfg_attr(feature = "zeroize", derive(zeroize::Zeroize))]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
-#[derive(Clone, Copy, Hash)]
+#[derive(Clone, Copy)]
 pub struct Hash([u8; OUT_LEN]);
 
 impl Hash {
+    /// The hash of the empty input, the same as `blake3::hash(b"")`.
+    ///
+    /// This is useful as a default or a sentinel value, for example for an empty file.
+    pub const EMPTY: Self = Self([
+        0xaf, 0x13, 0x49, 0xb9, 0xf5, 0xf9, 0xa1, 0xa6, 0xa0, 0x40, 0x4d, 0xea, 0x36, 0xdc, 0xc9,
+        0x49, 0x9b, 0xcb, 0x25, 0xc9, 0xad, 0xc1, 0x12, 0xb7, 0xcc, 0x9a, 0x93, 0xca, 0xe4, 0x1f,
+        0x32, 0x62,
+    ]);
+
     /// The raw bytes of the `Hash`. Note that byte arrays don't provide
     /// constant-time equality checking, so if  you need to compare hashes,
     /// prefer the `Hash` type.
@@ -235,6 +304,44 @@ impl Hash {
         Self(bytes)
     }
 
+    /// The first 16 bytes of the `Hash`, for non-cryptographic uses like sharding or hash table
+    /// keys.
+    ///
+    /// **This is not a secure digest.** At 16 bytes, finding a collision takes only about
+    /// 2<sup>64</sup> work. If you need a shorter output that's still meant to be secure, see
+    /// [`ShortHash`] and [`Hasher::finalize_short`].
+    #[inline]
+    pub fn truncate_128(&self) -> [u8; 16] {
+        *array_ref!(self.0, 0, 16)
+    }
+
+    /// The first 8 bytes of the `Hash`, for non-cryptographic uses like sharding or hash table
+    /// keys.
+    ///
+    /// **This is not a secure digest.** At 8 bytes, collisions are easy to find, and random
+    /// inputs are likely to collide after about 2<sup>32</sup> of them.
+    #[inline]
+    pub fn truncate_64(&self) -> [u8; 8] {
+        *array_ref!(self.0, 0, 8)
+    }
+
+    /// Create a `Hash` from 32 random bytes.
+    ///
+    /// The `rng` can be anything that implements `rand_core::RngCore`, which is the same trait as
+    /// [`rand::RngCore`](https://docs.rs/rand/0.8/rand/trait.RngCore.html). This is convenient for
+    /// tests and for generating keys and nonces, but note that the result is only as
+    /// unpredictable as `rng`. For secret keys, use a cryptographically secure RNG like
+    /// `rand::rngs::OsRng`.
+    ///
+    /// This method requires the `rand` Cargo feature, which is disabled by default but enabled
+    /// on [docs.rs](https://docs.rs).
+    #[cfg(feature = "rand")]
+    pub fn random(rng: &mut impl rand_core::RngCore) -> Self {
+        let mut bytes = [0; OUT_LEN];
+        rng.fill_bytes(&mut bytes);
+        Self(bytes)
+    }
+
     /// Encode a `Hash` in lowercase hexadecimal.
     ///
     /// The returned [`ArrayString`] is a fixed size and doesn't allocate memory
@@ -245,11 +352,7 @@ impl Hash {
     /// [`ArrayString`]: https://docs.rs/arrayvec/0.5.1/arrayvec/struct.ArrayString.html
     pub fn to_hex(&self) -> ArrayString<{ 2 * OUT_LEN }> {
         let mut s = ArrayString::new();
-        let table = b"0123456789abcdef";
-        for &b in self.0.iter() {
-            s.push(table[(b >> 4) as usize] as char);
-            s.push(table[(b & 0xf) as usize] as char);
-        }
+        push_hex(&mut s, &self.0);
         s
     }
 
@@ -263,17 +366,12 @@ impl Hash {
     /// Note that `Hash` also implements `FromStr`, so `Hash::from_hex("...")`
     /// is equivalent to `"...".parse()`.
     pub fn from_hex(hex: impl AsRef<[u8]>) -> Result<Self, HexError> {
-        fn hex_val(byte: u8) -> Result<u8, HexError> {
-            match byte {
-                b'A'..=b'F' => Ok(byte - b'A' + 10),
-                b'a'..=b'f' => Ok(byte - b'a' + 10),
-                b'0'..=b'9' => Ok(byte - b'0'),
-                _ => Err(HexError(HexErrorInner::InvalidByte(byte))),
-            }
-        }
         let hex_bytes: &[u8] = hex.as_ref();
         if hex_bytes.len() != OUT_LEN * 2 {
-            return Err(HexError(HexErrorInner::InvalidLen(hex_bytes.len())));
+            return Err(HexError(HexErrorInner::InvalidLen {
+                expected: OUT_LEN * 2,
+                received: hex_bytes.len(),
+            }));
         }
         let mut hash_bytes: [u8; OUT_LEN] = [0; OUT_LEN];
         for i in 0..OUT_LEN {
@@ -281,6 +379,331 @@ impl Hash {
         }
         Ok(Hash::from(hash_bytes))
     }
+
+    /// Compare a `Hash` to a hexadecimal string, as [`from_hex`](#method.from_hex) followed by
+    /// `==` would, but without allocating or returning an error.
+    ///
+    /// Both uppercase and lowercase ASCII are supported. Malformed input, including a length
+    /// other than 64, returns `false`. Decoding is byte by byte, and then the decoded bytes are
+    /// compared to the `Hash` in constant time, as [`ct::eq`] does.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let hash = blake3::hash(b"");
+    /// assert!(hash.eq_hex("af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262"));
+    /// assert!(!hash.eq_hex("af1349b9"));
+    /// ```
+    pub fn eq_hex(&self, hex: &str) -> bool {
+        let hex_bytes = hex.as_bytes();
+        if hex_bytes.len() != OUT_LEN * 2 {
+            return false;
+        }
+        let mut decoded = [0; OUT_LEN];
+        for (byte, pair) in decoded.iter_mut().zip(hex_bytes.chunks_exact(2)) {
+            match (hex_val(pair[0]), hex_val(pair[1])) {
+                (Ok(high), Ok(low)) => *byte = 16 * high + low,
+                _ => return false,
+            }
+        }
+        ct::eq_32(&self.0, &decoded)
+    }
+
+    /// Encode a `Hash` in lowercase hexadecimal, prefixed with a one-byte
+    /// algorithm tag.
+    ///
+    /// The output is the tag as two hex characters, followed by the 64
+    /// characters of [`to_hex`](#method.to_hex). This is a common layout in
+    /// storage formats that need to identify which hash function produced a
+    /// digest. As with `to_hex`, the returned [`ArrayString`] doesn't allocate.
+    ///
+    /// [`ArrayString`]: https://docs.rs/arrayvec/0.5.1/arrayvec/struct.ArrayString.html
+    pub fn to_tagged_hex(&self, tag: u8) -> ArrayString<{ 2 + 2 * OUT_LEN }> {
+        let mut s = ArrayString::new();
+        push_hex(&mut s, &[tag]);
+        push_hex(&mut s, &self.0);
+        s
+    }
+
+    /// Decode a tag and a `Hash` from the format produced by
+    /// [`to_tagged_hex`](#method.to_tagged_hex). Both uppercase and lowercase
+    /// ASCII bytes are supported.
+    ///
+    /// Any byte outside the ranges `'0'...'9'`, `'a'...'f'`, and `'A'...'F'`
+    /// results in an error. An input length other than 66 also results in an
+    /// error.
+    pub fn from_tagged_hex(hex: impl AsRef<[u8]>) -> Result<(u8, Self), HexError> {
+        let hex_bytes: &[u8] = hex.as_ref();
+        if hex_bytes.len() != 2 + OUT_LEN * 2 {
+            return Err(HexError(HexErrorInner::InvalidLen {
+                expected: 2 + OUT_LEN * 2,
+                received: hex_bytes.len(),
+            }));
+        }
+        let tag = 16 * hex_val(hex_bytes[0])? + hex_val(hex_bytes[1])?;
+        Ok((tag, Hash::from_hex(&hex_bytes[2..])?))
+    }
+
+    /// Encode a `Hash` in unpadded RFC 4648 base32, using the uppercase alphabet `A-Z2-7`.
+    ///
+    /// The output is 52 characters. Unlike hex, it's short enough to use as a filename on
+    /// most systems, and unlike base64, it doesn't depend on case, so it's safe on
+    /// case-insensitive filesystems. As with [`to_hex`](#method.to_hex), the returned
+    /// [`ArrayString`] doesn't allocate.
+    ///
+    /// [`ArrayString`]: https://docs.rs/arrayvec/0.5.1/arrayvec/struct.ArrayString.html
+    pub fn to_base32(&self) -> ArrayString<56> {
+        let mut s = ArrayString::new();
+        let mut buffer: u32 = 0;
+        let mut bits = 0;
+        for &b in self.0.iter() {
+            buffer = (buffer << 8) | b as u32;
+            bits += 8;
+            while bits >= 5 {
+                bits -= 5;
+                s.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+            }
+        }
+        // 256 isn't a multiple of 5, so there's 1 bit left over. Pad it with zeros.
+        debug_assert_eq!(bits, 1);
+        s.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+        s
+    }
+
+    /// Decode a `Hash` from unpadded RFC 4648 base32. Both uppercase and lowercase ASCII bytes
+    /// are supported.
+    ///
+    /// Any byte outside the ranges `'A'...'Z'`, `'a'...'z'`, and `'2'...'7'` results in an
+    /// error, including the `'='` padding character. An input length other than 52 also results
+    /// in an error, as does a final character with any of its unused low bits set, so that
+    /// every `Hash` has exactly one valid encoding (ignoring case).
+    pub fn from_base32(base32: impl AsRef<[u8]>) -> Result<Self, Base32Error> {
+        let base32_bytes: &[u8] = base32.as_ref();
+        if base32_bytes.len() != BASE32_LEN {
+            return Err(Base32Error(Base32ErrorInner::InvalidLen {
+                expected: BASE32_LEN,
+                received: base32_bytes.len(),
+            }));
+        }
+        let mut hash_bytes = [0; OUT_LEN];
+        let mut buffer: u32 = 0;
+        let mut bits = 0;
+        let mut i = 0;
+        for &c in base32_bytes {
+            buffer = (buffer << 5) | base32_val(c)? as u32;
+            bits += 5;
+            if bits >= 8 {
+                bits -= 8;
+                hash_bytes[i] = (buffer >> bits) as u8;
+                i += 1;
+            }
+        }
+        debug_assert_eq!(i, OUT_LEN);
+        if buffer & ((1 << bits) - 1) != 0 {
+            let last = base32_bytes[BASE32_LEN - 1];
+            return Err(Base32Error(Base32ErrorInner::NonzeroTrailingBits(last)));
+        }
+        Ok(Hash::from(hash_bytes))
+    }
+
+    /// Encode a `Hash` as a [multihash](https://multiformats.io/multihash/), the self-describing
+    /// digest format used by IPFS and other multiformats systems.
+    ///
+    /// The output is the BLAKE3 multihash code `0x1e` and the digest length `0x20`, each a
+    /// one-byte varint, followed by the 32 bytes of the hash.
+    pub fn to_multihash(&self) -> [u8; MULTIHASH_LEN] {
+        let mut bytes = [0; MULTIHASH_LEN];
+        bytes[..2].copy_from_slice(&MULTIHASH_PREFIX);
+        bytes[2..].copy_from_slice(&self.0);
+        bytes
+    }
+
+    /// Decode a `Hash` from the multihash format produced by
+    /// [`to_multihash`](#method.to_multihash).
+    ///
+    /// A code other than `0x1e`, a digest length other than `0x20`, or an input length other than
+    /// 34 results in an error. BLAKE3 multihashes with other digest lengths are valid in general,
+    /// but they can't be represented as a `Hash`.
+    pub fn from_multihash(bytes: &[u8]) -> Result<Self, MultihashError> {
+        if bytes.len() != MULTIHASH_LEN {
+            return Err(MultihashError(MultihashErrorInner::InvalidLen {
+                expected: MULTIHASH_LEN,
+                received: bytes.len(),
+            }));
+        }
+        if bytes[0] != MULTIHASH_PREFIX[0] {
+            return Err(MultihashError(MultihashErrorInner::UnsupportedCode(
+                bytes[0],
+            )));
+        }
+        if bytes[1] != MULTIHASH_PREFIX[1] {
+            return Err(MultihashError(MultihashErrorInner::UnsupportedDigestLen(
+                bytes[1],
+            )));
+        }
+        Ok(Hash(*array_ref!(bytes, 2, OUT_LEN)))
+    }
+
+    /// Count the number of bits that differ between two hashes.
+    ///
+    /// This is for debugging and diagnostics only. Any change to the input of a cryptographic
+    /// hash is expected to flip about half of the output bits, so the distance between two
+    /// hashes says nothing about how similar their inputs were. To check whether two hashes are
+    /// equal, use `==`, which is constant-time.
+    pub fn hamming_distance(&self, other: &Hash) -> u32 {
+        self.0
+            .iter()
+            .zip(other.0.iter())
+            .map(|(a, b)| (a ^ b).count_ones())
+            .sum()
+    }
+
+    /// Split a `Hash` into two `u128`s, `(high, low)`, reading the 32 bytes as a big-endian
+    /// integer. So for example the high half is the first 16 bytes, and it sorts the same way as
+    /// the hex representation of those bytes.
+    ///
+    /// Note that `u128` doesn't provide constant-time equality checking. Also note that a single
+    /// 128-bit half of the hash provides only 64 bits of collision resistance. That's fine for
+    /// some in-memory indexes, but it's not enough for content addressing.
+    pub fn to_u128_pair(&self) -> (u128, u128) {
+        (
+            u128::from_be_bytes(*array_ref!(self.0, 0, 16)),
+            u128::from_be_bytes(*array_ref!(self.0, 16, 16)),
+        )
+    }
+
+    /// Create a `Hash` from two `u128`s, `(high, low)`. This is the inverse of
+    /// [`to_u128_pair`](#method.to_u128_pair).
+    pub fn from_u128_pair((high, low): (u128, u128)) -> Self {
+        let mut bytes = [0; OUT_LEN];
+        *array_mut_ref!(bytes, 0, 16) = high.to_be_bytes();
+        *array_mut_ref!(bytes, 16, 16) = low.to_be_bytes();
+        Self(bytes)
+    }
+
+    /// Read the 32 raw bytes of a `Hash` from a
+    /// [`std::io::Read`](https://doc.rust-lang.org/std/io/trait.Read.html) implementation.
+    ///
+    /// This uses [`read_exact`](https://doc.rust-lang.org/std/io/trait.Read.html#method.read_exact),
+    /// so a reader with fewer than 32 bytes remaining results in an error of kind
+    /// [`UnexpectedEof`](std::io::ErrorKind::UnexpectedEof).
+    ///
+    /// This method requires the `std` Cargo feature, which is enabled by default.
+    #[cfg(feature = "std")]
+    pub fn from_reader(reader: &mut impl std::io::Read) -> std::io::Result<Self> {
+        let mut bytes = [0; OUT_LEN];
+        reader.read_exact(&mut bytes)?;
+        Ok(Self::from_bytes(bytes))
+    }
+
+    /// Write the 32 raw bytes of a `Hash` to a
+    /// [`std::io::Write`](https://doc.rust-lang.org/std/io/trait.Write.html) implementation. This
+    /// is the inverse of [`from_reader`](#method.from_reader).
+    ///
+    /// This method requires the `std` Cargo feature, which is enabled by default.
+    #[cfg(feature = "std")]
+    pub fn write_to(&self, writer: &mut impl std::io::Write) -> std::io::Result<()> {
+        writer.write_all(&self.0)
+    }
+}
+
+/// Write the lowercase hexadecimal encoding of each hash to `writer`, one per line.
+///
+/// Each hash is encoded into a reused buffer on the stack, so this doesn't allocate, and the
+/// output is the same as writing [`Hash::to_hex`] and `"\n"` for each hash. For large batches,
+/// `writer` should usually be a
+/// [`BufWriter`](https://doc.rust-lang.org/std/io/struct.BufWriter.html), because this makes one
+/// `write_all` call per hash.
+///
+/// This function requires the `std` Cargo feature, which is enabled by default.
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// let hashes = [blake3::hash(b"foo"), blake3::hash(b"bar")];
+/// let mut out = Vec::new();
+/// blake3::write_hashes_hex(&hashes, &mut out)?;
+/// assert_eq!(out.len(), 2 * 65);
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "std")]
+pub fn write_hashes_hex(hashes: &[Hash], writer: &mut impl std::io::Write) -> std::io::Result<()> {
+    let mut line = [b'\n'; 2 * OUT_LEN + 1];
+    for hash in hashes {
+        line[..2 * OUT_LEN].copy_from_slice(hash.to_hex().as_bytes());
+        writer.write_all(&line)?;
+    }
+    Ok(())
+}
+
+/// Hash a directory tree into a single [`Hash`], combining the relative path and the contents of
+/// each file.
+///
+/// The result only depends on the names and contents of the regular files under `root`, so it's
+/// reproducible across machines and platforms:
+///
+/// - Each file's path is taken relative to `root`, with components joined by `/` on every
+///   platform. Paths must be valid UTF-8.
+/// - Files are hashed in ascending byte order of those relative paths, regardless of the order
+///   the filesystem lists them in.
+/// - For each file, a single [`Hasher`] receives the relative path and then the file contents,
+///   each framed as in [`Hasher::update_framed`], that is, prefixed with its length as 8
+///   little-endian bytes.
+///
+/// Directories only contribute the files inside them, so empty directories don't affect the
+/// result, and neither do file metadata like permissions and timestamps. Symbolic links aren't
+/// followed, and any file that isn't a regular file or a directory is an error, as is a path that
+/// isn't valid UTF-8. It's also an error if a file shrinks or grows while it's being read.
+///
+/// This function requires the `std` Cargo feature, which is enabled by default.
+#[cfg(feature = "std")]
+pub fn hash_dir(root: impl AsRef<std::path::Path>) -> std::io::Result<Hash> {
+    let mut hasher = Hasher::new();
+    for (relative_path, full_path) in io::sorted_files(root.as_ref())? {
+        hasher.update_framed(relative_path.as_bytes());
+        let file = std::fs::File::open(&full_path)?;
+        let len = file.metadata()?.len();
+        hasher.update(&len.to_le_bytes());
+        io::copy_exact(file, &mut hasher, len)
+            .map_err(|e| std::io::Error::new(e.kind(), format!("{}: {}", relative_path, e)))?;
+    }
+    Ok(hasher.finalize())
+}
+
+// Append `bytes` to `s` in lowercase hexadecimal. Panics if `s` doesn't have room.
+fn push_hex<const CAP: usize>(s: &mut ArrayString<CAP>, bytes: &[u8]) {
+    let table = b"0123456789abcdef";
+    for &b in bytes {
+        s.push(table[(b >> 4) as usize] as char);
+        s.push(table[(b & 0xf) as usize] as char);
+    }
+}
+
+fn hex_val(byte: u8) -> Result<u8, HexError> {
+    match byte {
+        b'A'..=b'F' => Ok(byte - b'A' + 10),
+        b'a'..=b'f' => Ok(byte - b'a' + 10),
+        b'0'..=b'9' => Ok(byte - b'0'),
+        _ => Err(HexError(HexErrorInner::InvalidByte(byte))),
+    }
+}
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+// 256 bits in 5-bit characters, rounded up. usize::div_ceil needs Rust 1.73, which is newer
+// than our MSRV.
+#[allow(clippy::manual_div_ceil)]
+const BASE32_LEN: usize = (8 * OUT_LEN + 4) / 5;
+
+fn base32_val(byte: u8) -> Result<u8, Base32Error> {
+    match byte {
+        b'A'..=b'Z' => Ok(byte - b'A'),
+        b'a'..=b'z' => Ok(byte - b'a'),
+        b'2'..=b'7' => Ok(byte - b'2' + 26),
+        _ => Err(Base32Error(Base32ErrorInner::InvalidByte(byte))),
+    }
 }
 
 impl From<[u8; OUT_LEN]> for Hash {
@@ -305,29 +728,20 @@ impl core::str::FromStr for Hash {
     }
 }
 
-// A proper implementation of constant time equality is tricky, and we get it from the
-// constant_time_eq crate instead of rolling our own. However, that crate isn't compatible with
-// Miri, so we roll our own just for that.
-#[cfg(miri)]
-fn constant_time_eq_miri(a: &[u8], b: &[u8]) -> bool {
-    if a.len() != b.len() {
-        return false;
-    }
-    let mut x = 0;
-    for i in 0..a.len() {
-        x |= a[i] ^ b[i];
+impl TryFrom<&str> for Hash {
+    type Error = HexError;
+
+    /// This is equivalent to [`from_hex`](#method.from_hex).
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        Hash::from_hex(s)
     }
-    x == 0
 }
 
 /// This implementation is constant-time.
 impl PartialEq for Hash {
     #[inline]
     fn eq(&self, other: &Hash) -> bool {
-        #[cfg(miri)]
-        return constant_time_eq_miri(&self.0, &other.0);
-        #[cfg(not(miri))]
-        constant_time_eq::constant_time_eq_32(&self.0, &other.0)
+        ct::eq_32(&self.0, &other.0)
     }
 }
 
@@ -335,10 +749,7 @@ impl PartialEq for Hash {
 impl PartialEq<[u8; OUT_LEN]> for Hash {
     #[inline]
     fn eq(&self, other: &[u8; OUT_LEN]) -> bool {
-        #[cfg(miri)]
-        return constant_time_eq_miri(&self.0, other);
-        #[cfg(not(miri))]
-        constant_time_eq::constant_time_eq_32(&self.0, other)
+        ct::eq_32(&self.0, other)
     }
 }
 
@@ -346,15 +757,23 @@ impl PartialEq<[u8; OUT_LEN]> for Hash {
 impl PartialEq<[u8]> for Hash {
     #[inline]
     fn eq(&self, other: &[u8]) -> bool {
-        #[cfg(miri)]
-        return constant_time_eq_miri(&self.0, other);
-        #[cfg(not(miri))]
-        constant_time_eq::constant_time_eq(&self.0, other)
+        ct::eq_slices(&self.0, other)
     }
 }
 
 impl Eq for Hash {}
 
+/// This implementation writes the 32 raw bytes, with no length prefix. (The derived
+/// implementation for an array would add a `usize` prefix, whose byte representation depends on
+/// the target.) So for a given [`Hasher`](core::hash::Hasher) with fixed keys, the result is the
+/// same on every platform.
+impl core::hash::Hash for Hash {
+    #[inline]
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        state.write(&self.0);
+    }
+}
+
 impl fmt::Display for Hash {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         // Formatting field as `&str` to reduce code size since the `Debug`
@@ -379,7 +798,8 @@ impl fmt::Debug for Hash {
     }
 }
 
-/// The error type for [`Hash::from_hex`].
+/// The error type for [`Hash::from_hex`], [`Hash::from_tagged_hex`], and
+/// [`ShortHash::from_hex`].
 ///
 /// The `.to_string()` representation of this error currently distinguishes between bad length
 /// errors and bad character errors. This is to help with logging and debugging, but it isn't a
@@ -390,7 +810,7 @@ pub struct HexError(HexErrorInner);
 #[derive(Clone, Debug)]
 enum HexErrorInner {
     InvalidByte(u8),
-    InvalidLen(usize),
+    InvalidLen { expected: usize, received: usize },
 }
 
 impl fmt::Display for HexError {
@@ -403,8 +823,8 @@ impl fmt::Display for HexError {
                     write!(f, "invalid hex character: 0x{:x}", byte)
                 }
             }
-            HexErrorInner::InvalidLen(len) => {
-                write!(f, "expected 64 hex bytes, received {}", len)
+            HexErrorInner::InvalidLen { expected, received } => {
+                write!(f, "expected {} hex bytes, received {}", expected, received)
             }
         }
     }
@@ -413,6 +833,142 @@ impl fmt::Display for HexError {
 #[cfg(feature = "std")]
 impl std::error::Error for HexError {}
 
+/// The error type for [`Hash::from_base32`].
+///
+/// The `.to_string()` representation of this error currently distinguishes between the different
+/// kinds of errors. This is to help with logging and debugging, but it isn't a stable API detail,
+/// and it may change at any time.
+#[derive(Clone, Debug)]
+pub struct Base32Error(Base32ErrorInner);
+
+#[derive(Clone, Debug)]
+enum Base32ErrorInner {
+    InvalidByte(u8),
+    InvalidLen { expected: usize, received: usize },
+    NonzeroTrailingBits(u8),
+}
+
+impl fmt::Display for Base32Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Base32ErrorInner::InvalidByte(byte) => {
+                if byte < 128 {
+                    write!(f, "invalid base32 character: {:?}", byte as char)
+                } else {
+                    write!(f, "invalid base32 character: 0x{:x}", byte)
+                }
+            }
+            Base32ErrorInner::InvalidLen { expected, received } => {
+                write!(
+                    f,
+                    "expected {} base32 bytes, received {}",
+                    expected, received
+                )
+            }
+            Base32ErrorInner::NonzeroTrailingBits(byte) => {
+                write!(
+                    f,
+                    "nonzero trailing bits in final base32 character: {:?}",
+                    byte as char
+                )
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Base32Error {}
+
+// The domain separation tag for Hasher::update_file_with_len. The high bit of the last byte makes
+// it larger than isize::MAX as a little-endian u64, so it can't be an update_framed length.
+#[cfg(feature = "std")]
+const FILE_WITH_LEN_TAG: &[u8; 8] = b"filelen\xff";
+
+// The BLAKE3 multihash code and the digest length, each as a one-byte varint.
+const MULTIHASH_PREFIX: [u8; 2] = [0x1e, OUT_LEN as u8];
+
+/// The length of [`Hash::to_multihash`], the two-byte prefix plus the 32-byte hash.
+pub const MULTIHASH_LEN: usize = 2 + OUT_LEN;
+
+/// The error type for [`Hash::from_multihash`].
+///
+/// The `.to_string()` representation of this error currently distinguishes between the different
+/// kinds of errors. This is to help with logging and debugging, but it isn't a stable API detail,
+/// and it may change at any time.
+#[derive(Clone, Debug)]
+pub struct MultihashError(MultihashErrorInner);
+
+#[derive(Clone, Debug)]
+enum MultihashErrorInner {
+    InvalidLen { expected: usize, received: usize },
+    UnsupportedCode(u8),
+    UnsupportedDigestLen(u8),
+}
+
+impl fmt::Display for MultihashError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            MultihashErrorInner::InvalidLen { expected, received } => {
+                write!(
+                    f,
+                    "expected {} multihash bytes, received {}",
+                    expected, received
+                )
+            }
+            MultihashErrorInner::UnsupportedCode(code) => {
+                write!(f, "expected BLAKE3 multihash code 0x1e, found 0x{:x}", code)
+            }
+            MultihashErrorInner::UnsupportedDigestLen(len) => {
+                write!(f, "expected multihash digest length 32, found {}", len)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MultihashError {}
+
+/// The length of [`OutputReader::to_output_bytes`].
+pub const OUTPUT_BYTES_LEN: usize = 32 + BLOCK_LEN + 1 + 1;
+
+/// The error type for [`OutputReader::from_output_bytes`].
+///
+/// The `.to_string()` representation of this error currently distinguishes between bad length
+/// errors and bad field errors. This is to help with logging and debugging, but it isn't a stable
+/// API detail, and it may change at any time.
+#[derive(Clone, Debug)]
+pub struct OutputBytesError(OutputBytesErrorInner);
+
+#[derive(Clone, Debug)]
+enum OutputBytesErrorInner {
+    InvalidLen { expected: usize, received: usize },
+    BlockLenTooLong(u8),
+    UnknownFlags(u8),
+}
+
+impl fmt::Display for OutputBytesError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            OutputBytesErrorInner::InvalidLen { expected, received } => {
+                write!(
+                    f,
+                    "expected {} output bytes, received {}",
+                    expected, received
+                )
+            }
+            OutputBytesErrorInner::BlockLenTooLong(len) => {
+                write!(f, "block length {} is longer than {}", len, BLOCK_LEN)
+            }
+            OutputBytesErrorInner::UnknownFlags(flags) => {
+                write!(f, "unknown domain flags 0x{:02x}", flags)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for OutputBytesError {}
+
 // Each chunk or parent node can produce either a 32-byte chaining value or, by
 // setting the ROOT flag, any number of final output bytes. The Output struct
 // captures the state just prior to choosing between those two possibilities.
@@ -458,6 +1014,56 @@ impl Output {
             self.flags | ROOT,
         )
     }
+
+    // The input chaining value as little-endian words, then the block, the block length, and the
+    // flags. The counter is left out, because for a root output it's the output block index,
+    // which belongs to the reader's position rather than to the output itself.
+    fn to_bytes(&self) -> [u8; OUTPUT_BYTES_LEN] {
+        let mut bytes = [0; OUTPUT_BYTES_LEN];
+        {
+            let (cv, block, block_len, flags) = mut_array_refs!(&mut bytes, 32, BLOCK_LEN, 1, 1);
+            *cv = platform::le_bytes_from_words_32(&self.input_chaining_value);
+            *block = self.block;
+            block_len[0] = self.block_len;
+            flags[0] = self.flags;
+        }
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8], counter: u64) -> Result<Self, OutputBytesError> {
+        let bytes: &[u8; OUTPUT_BYTES_LEN] = bytes.try_into().map_err(|_| {
+            OutputBytesError(OutputBytesErrorInner::InvalidLen {
+                expected: OUTPUT_BYTES_LEN,
+                received: bytes.len(),
+            })
+        })?;
+        let (cv, block, block_len, flags) = array_refs!(bytes, 32, BLOCK_LEN, 1, 1);
+        if block_len[0] as usize > BLOCK_LEN {
+            return Err(OutputBytesError(OutputBytesErrorInner::BlockLenTooLong(
+                block_len[0],
+            )));
+        }
+        // ROOT is never stored. It's added when output blocks are computed.
+        let known_flags = CHUNK_START
+            | CHUNK_END
+            | PARENT
+            | KEYED_HASH
+            | DERIVE_KEY_CONTEXT
+            | DERIVE_KEY_MATERIAL;
+        if flags[0] & !known_flags != 0 {
+            return Err(OutputBytesError(OutputBytesErrorInner::UnknownFlags(
+                flags[0],
+            )));
+        }
+        Ok(Self {
+            input_chaining_value: platform::words_from_le_bytes_32(cv),
+            block: *block,
+            block_len: block_len[0],
+            counter,
+            flags: flags[0],
+            platform: Platform::detect(),
+        })
+    }
 }
 
 #[derive(Clone)]
@@ -498,6 +1104,22 @@ impl ChunkState {
         *input = &input[take..];
     }
 
+    // Append input to a partially filled block buffer, if it fits without overflowing the block.
+    // This is the fast path for many tiny updates: nothing gets compressed, and since the chunk
+    // isn't empty, the caller doesn't need to merge the CV stack either. Returns false without
+    // changing anything if the input doesn't fit, or if the buffer is empty, which includes the
+    // case of an empty chunk.
+    #[inline]
+    fn try_append_to_buf(&mut self, input: &[u8]) -> bool {
+        let buf_len = self.buf_len as usize;
+        if buf_len == 0 || input.len() > BLOCK_LEN - buf_len {
+            return false;
+        }
+        self.buf[buf_len..][..input.len()].copy_from_slice(input);
+        self.buf_len += input.len() as u8;
+        true
+    }
+
     fn start_flag(&self) -> u8 {
         if self.blocks_compressed == 0 {
             CHUNK_START
@@ -729,6 +1351,10 @@ fn compress_parents_parallel(
 // Why not just have the caller split the input on the first update(), instead
 // of implementing this special rule? Because we don't want to limit SIMD or
 // multithreading parallelism for that update().
+//
+// The join_depth argument is the number of recursion levels that may still use
+// J. Below that, both halves are hashed serially on the current thread, which
+// bounds the number of tasks Rayon has to schedule. usize::MAX means no limit.
 fn compress_subtree_wide<J: join::Join>(
     input: &[u8],
     key: &CVWords,
@@ -736,6 +1362,7 @@ fn compress_subtree_wide<J: join::Join>(
     flags: u8,
     platform: Platform,
     out: &mut [u8],
+    join_depth: usize,
 ) -> usize {
     // Note that the single chunk case does *not* bump the SIMD degree up to 2
     // when it is 1. This allows Rayon the option of multithreading even the
@@ -754,7 +1381,10 @@ fn compress_subtree_wide<J: join::Join>(
 
     // Make space for the child outputs. Here we use MAX_SIMD_DEGREE_OR_2 to
     // account for the special case of returning 2 outputs when the SIMD degree
-    // is 1.
+    // is 1. This scratch space lives on the stack, and it's at most 1 KiB, so
+    // there's no allocation to amortize across calls to update(). Keeping it
+    // in the Hasher instead would make every Hasher bigger without measurably
+    // speeding up steady streams. (See bench_incremental_simd_windows.)
     let mut cv_array = [0; 2 * MAX_SIMD_DEGREE_OR_2 * OUT_LEN];
     let degree = if left.len() == CHUNK_LEN {
         // The "simd_degree=1 and we're at the leaf nodes" case.
@@ -766,11 +1396,22 @@ fn compress_subtree_wide<J: join::Join>(
     let (left_out, right_out) = cv_array.split_at_mut(degree * OUT_LEN);
 
     // Recurse! For update_rayon(), this is where we take advantage of RayonJoin and use multiple
-    // threads.
-    let (left_n, right_n) = J::join(
-        || compress_subtree_wide::<J>(left, key, chunk_counter, flags, platform, left_out),
-        || compress_subtree_wide::<J>(right, key, right_chunk_counter, flags, platform, right_out),
-    );
+    // threads, until we run out of join depth.
+    let mut left_subtree = |depth| {
+        compress_subtree_wide::<J>(left, key, chunk_counter, flags, platform, left_out, depth)
+    };
+    let mut right_subtree = |depth| {
+        let counter = right_chunk_counter;
+        compress_subtree_wide::<J>(right, key, counter, flags, platform, right_out, depth)
+    };
+    let (left_n, right_n) = if join_depth > 0 {
+        J::join(
+            || left_subtree(join_depth - 1),
+            || right_subtree(join_depth - 1),
+        )
+    } else {
+        (left_subtree(0), right_subtree(0))
+    };
 
     // The special case again. If simd_degree=1, then we'll have left_n=1 and
     // right_n=1. Rather than compressing them into a single output, return
@@ -809,11 +1450,19 @@ fn compress_subtree_to_parent_node<J: join::Join>(
     chunk_counter: u64,
     flags: u8,
     platform: Platform,
+    join_depth: usize,
 ) -> [u8; BLOCK_LEN] {
     debug_assert!(input.len() > CHUNK_LEN);
     let mut cv_array = [0; MAX_SIMD_DEGREE_OR_2 * OUT_LEN];
-    let mut num_cvs =
-        compress_subtree_wide::<J>(input, &key, chunk_counter, flags, platform, &mut cv_array);
+    let mut num_cvs = compress_subtree_wide::<J>(
+        input,
+        key,
+        chunk_counter,
+        flags,
+        platform,
+        &mut cv_array,
+        join_depth,
+    );
     debug_assert!(num_cvs >= 2);
 
     // If MAX_SIMD_DEGREE is greater than 2 and there's enough input,
@@ -831,7 +1480,29 @@ fn compress_subtree_to_parent_node<J: join::Join>(
 // Hash a complete input all at once. Unlike compress_subtree_wide() and
 // compress_subtree_to_parent_node(), this function handles the 1 chunk case.
 fn hash_all_at_once<J: join::Join>(input: &[u8], key: &CVWords, flags: u8) -> Output {
-    let platform = Platform::detect();
+    hash_all_at_once_with_platform::<J>(input, key, flags, Platform::detect())
+}
+
+fn hash_all_at_once_with_platform<J: join::Join>(
+    input: &[u8],
+    key: &CVWords,
+    flags: u8,
+    platform: Platform,
+) -> Output {
+    // If the whole input is one block, it's a single compression. Skip the
+    // ChunkState and build the Output directly.
+    if input.len() <= BLOCK_LEN {
+        let mut block = [0; BLOCK_LEN];
+        block[..input.len()].copy_from_slice(input);
+        return Output {
+            input_chaining_value: *key,
+            block,
+            block_len: input.len() as u8,
+            counter: 0,
+            flags: flags | CHUNK_START | CHUNK_END,
+            platform,
+        };
+    }
 
     // If the whole subtree is one chunk, hash it directly with a ChunkState.
     if input.len() <= CHUNK_LEN {
@@ -844,7 +1515,7 @@ fn hash_all_at_once<J: join::Join>(input: &[u8], key: &CVWords, flags: u8) -> Ou
     // compress_subtree_to_parent_node().
     Output {
         input_chaining_value: *key,
-        block: compress_subtree_to_parent_node::<J>(input, key, 0, flags, platform),
+        block: compress_subtree_to_parent_node::<J>(input, key, 0, flags, platform, usize::MAX),
         block_len: BLOCK_LEN as u8,
         counter: 0,
         flags: flags | PARENT,
@@ -866,25 +1537,125 @@ pub fn hash(input: &[u8]) -> Hash {
     hash_all_at_once::<join::SerialJoin>(input, IV, 0).root_hash()
 }
 
-/// The keyed hash function.
+/// Hash a list of records into a single root, keeping the boundaries between records.
 ///
-/// This is suitable for use as a message authentication code, for example to
-/// replace an HMAC instance. In that use case, the constant-time equality
-/// checking provided by [`Hash`](struct.Hash.html) is almost always a security
-/// requirement, and callers need to be careful not to compare MACs as raw
-/// bytes.
+/// Each record is hashed into a leaf with [`hash`]. The leaves are then combined pairwise into a
+/// binary tree, using the same parent node function as BLAKE3 itself. The tree has the same shape
+/// as a BLAKE3 tree with one chunk per record: the left subtree of every parent holds the largest
+/// power-of-2 number of leaves that leaves at least one for the right subtree. The topmost parent
+/// is finalized as a root node.
 ///
-/// For output sizes other than 32 bytes, see [`Hasher::new_keyed`],
-/// [`Hasher::finalize_xof`], and [`OutputReader`].
+/// This is different from hashing the records concatenated together, which loses the boundaries
+/// between them. Changing, adding, removing, or reordering records changes the root.
 ///
-/// This function is always single-threaded. For multithreading support, see
-/// [`Hasher::new_keyed`] and
-/// [`Hasher::update_rayon`](struct.Hasher.html#method.update_rayon).
-pub fn keyed_hash(key: &[u8; KEY_LEN], input: &[u8]) -> Hash {
-    let key_words = platform::words_from_le_bytes_32(key);
-    hash_all_at_once::<join::SerialJoin>(input, &key_words, KEYED_HASH).root_hash()
-}
-
+/// A list of one record has the same root as [`hash`] of that record. The empty list has the same
+/// root as a list containing one empty record, which is `hash(b"")`. Callers that need to tell
+/// those two apart should make sure their records are non-empty, for example with a length or
+/// type prefix.
+///
+/// # Example
+///
+/// ```
+/// let root = blake3::hash_list(&[b"foo", b"bar"]);
+/// assert_ne!(root, blake3::hash(b"foobar"));
+/// assert_ne!(root, blake3::hash_list(&[b"foob", b"ar"]));
+/// ```
+pub fn hash_list(records: &[&[u8]]) -> Hash {
+    if records.is_empty() {
+        return hash(b"");
+    }
+    hash_list_subtree(records, true)
+}
+
+fn hash_list_subtree(records: &[&[u8]], is_root: bool) -> Hash {
+    debug_assert!(!records.is_empty());
+    if records.len() == 1 {
+        return hash(records[0]);
+    }
+    let (left, right) = records.split_at(largest_power_of_two_leq(records.len() - 1));
+    guts::parent_cv(
+        &hash_list_subtree(left, false),
+        &hash_list_subtree(right, false),
+        is_root,
+    )
+}
+
+/// Undocumented and unstable, for fuzzing only.
+///
+/// Feed each of `chunks` to a [`Hasher`] with a separate `update` call, and
+/// assert that the result matches both `reference` and [`hash`] applied to
+/// the concatenation of all the chunks. This is the harness behind the
+/// targets in `fuzz/`, which pass the reference implementation. Arbitrary
+/// chunk boundaries exercise the streaming code paths that [`hash`] skips.
+#[cfg(all(feature = "std", any(test, fuzzing, feature = "fuzzing")))]
+#[doc(hidden)]
+pub fn fuzz_update_chunks(chunks: &[Vec<u8>], reference: impl FnOnce(&[u8]) -> Hash) {
+    let mut hasher = Hasher::new();
+    let mut concatenated = Vec::new();
+    for chunk in chunks {
+        hasher.update(chunk);
+        concatenated.extend_from_slice(chunk);
+    }
+    let expected = reference(&concatenated);
+    assert_eq!(expected, hasher.finalize(), "incremental hash mismatch");
+    assert_eq!(expected, hash(&concatenated), "all-at-once hash mismatch");
+}
+
+/// As [`hash`], but using a backend chosen at compile time, rather than the one detected at
+/// runtime.
+///
+/// `P` is one of the types in [`backend`], and only the backends enabled for the whole build
+/// exist. This is for callers who know their target and want to skip CPU feature detection on
+/// every call. The output is always the same as [`hash`].
+///
+/// Note that this only skips detection. Internally the backend is still passed around as a
+/// value, and whether the compiler resolves each dispatch on it statically depends on inlining,
+/// which isn't guaranteed.
+///
+/// # Example
+///
+/// ```
+/// let hash = blake3::hash_with::<blake3::backend::Portable>(b"foo");
+/// assert_eq!(hash, blake3::hash(b"foo"));
+/// ```
+pub fn hash_with<P: backend::PlatformTrait>(input: &[u8]) -> Hash {
+    hash_all_at_once_with_platform::<join::SerialJoin>(input, IV, 0, P::PLATFORM).root_hash()
+}
+
+/// Undocumented and unstable, for benchmarks only.
+///
+/// As [`hash`], but using a specific platform implementation rather than the
+/// one detected at runtime. If `platform` isn't supported on the current
+/// machine, this falls back to the portable implementation.
+#[doc(hidden)]
+pub fn hash_with_platform(input: &[u8], platform: Platform) -> Hash {
+    let platform = if platform.is_available() {
+        platform
+    } else {
+        Platform::portable()
+    };
+    hash_all_at_once_with_platform::<join::SerialJoin>(input, IV, 0, platform).root_hash()
+}
+
+/// The keyed hash function.
+///
+/// This is suitable for use as a message authentication code, for example to
+/// replace an HMAC instance. In that use case, the constant-time equality
+/// checking provided by [`Hash`](struct.Hash.html) is almost always a security
+/// requirement, and callers need to be careful not to compare MACs as raw
+/// bytes.
+///
+/// For output sizes other than 32 bytes, see [`Hasher::new_keyed`],
+/// [`Hasher::finalize_xof`], and [`OutputReader`].
+///
+/// This function is always single-threaded. For multithreading support, see
+/// [`Hasher::new_keyed`] and
+/// [`Hasher::update_rayon`](struct.Hasher.html#method.update_rayon).
+pub fn keyed_hash(key: &[u8; KEY_LEN], input: &[u8]) -> Hash {
+    let key_words = platform::words_from_le_bytes_32(key);
+    hash_all_at_once::<join::SerialJoin>(input, &key_words, KEYED_HASH).root_hash()
+}
+
 /// The key derivation function.
 ///
 /// Given cryptographic key material of any length and a context string of any
@@ -915,19 +1686,66 @@ pub fn keyed_hash(key: &[u8; KEY_LEN], input: &[u8]) -> Hash {
 /// For output sizes other than 32 bytes, see [`Hasher::new_derive_key`],
 /// [`Hasher::finalize_xof`], and [`OutputReader`].
 ///
+/// To derive many keys with the same context string, see [`DeriveKeyContext`].
+///
 /// This function is always single-threaded. For multithreading support, see
 /// [`Hasher::new_derive_key`] and
 /// [`Hasher::update_rayon`](struct.Hasher.html#method.update_rayon).
 ///
 /// [Argon2]: https://en.wikipedia.org/wiki/Argon2
 pub fn derive_key(context: &str, key_material: &[u8]) -> [u8; OUT_LEN] {
-    let context_key =
-        hash_all_at_once::<join::SerialJoin>(context.as_bytes(), IV, DERIVE_KEY_CONTEXT)
-            .root_hash();
-    let context_key_words = platform::words_from_le_bytes_32(context_key.as_bytes());
-    hash_all_at_once::<join::SerialJoin>(key_material, &context_key_words, DERIVE_KEY_MATERIAL)
-        .root_hash()
-        .0
+    DeriveKeyContext::new(context).derive(key_material)
+}
+
+/// A [`derive_key`] context string, hashed once and ready for many derivations.
+///
+/// Both [`derive_key`] and [`Hasher::new_derive_key`] start by hashing the context string into a
+/// key, before they look at the key material. If you derive many keys with the same context,
+/// `DeriveKeyContext` does that step once and reuses the result. The output is exactly the same
+/// as calling `derive_key` with the same context string each time.
+///
+/// # Example
+///
+/// ```
+/// let context = blake3::DeriveKeyContext::new("example.com 2019-12-25 16:18:03 session tokens v1");
+/// for key_material in [b"first session", b"other session"] {
+///     assert_eq!(
+///         context.derive(key_material),
+///         blake3::derive_key("example.com 2019-12-25 16:18:03 session tokens v1", key_material),
+///     );
+/// }
+/// ```
+#[cfg_attr(feature = "zeroize", derive(zeroize::Zeroize))]
+#[derive(Clone, Debug)]
+pub struct DeriveKeyContext {
+    key_words: CVWords,
+}
+
+impl DeriveKeyContext {
+    /// Hash a context string. The context string should be hardcoded, globally unique, and
+    /// application-specific. See [`derive_key`].
+    pub fn new(context: &str) -> Self {
+        let context_key =
+            hash_all_at_once::<join::SerialJoin>(context.as_bytes(), IV, DERIVE_KEY_CONTEXT)
+                .root_hash();
+        Self {
+            key_words: platform::words_from_le_bytes_32(context_key.as_bytes()),
+        }
+    }
+
+    /// Derive a 32-byte key from `key_material`. This is the same as [`derive_key`] with the
+    /// original context string, and like that function it's always single-threaded.
+    pub fn derive(&self, key_material: &[u8]) -> [u8; OUT_LEN] {
+        hash_all_at_once::<join::SerialJoin>(key_material, &self.key_words, DERIVE_KEY_MATERIAL)
+            .root_hash()
+            .0
+    }
+
+    /// Construct a new [`Hasher`] for the key derivation function with this context. This is the
+    /// same as [`Hasher::new_derive_key`] with the original context string.
+    pub fn new_hasher(&self) -> Hasher {
+        Hasher::new_internal(&self.key_words, DERIVE_KEY_MATERIAL)
+    }
 }
 
 fn parent_node_output(
@@ -995,6 +1813,8 @@ pub struct Hasher {
     // we don't know whether more input is coming. This is different from how
     // the reference implementation does things.
     cv_stack: ArrayVec<CVBytes, { MAX_DEPTH + 1 }>,
+    // The limit enforced by try_update(), if any.
+    max_input: Option<u64>,
 }
 
 impl Hasher {
@@ -1003,6 +1823,7 @@ impl Hasher {
             key: *key,
             chunk_state: ChunkState::new(key, 0, flags, Platform::detect()),
             cv_stack: ArrayVec::new(),
+            max_input: None,
         }
     }
 
@@ -1020,17 +1841,79 @@ impl Hasher {
         Self::new_internal(&key_words, KEYED_HASH)
     }
 
+    /// Set up the keystream and the one-time authentication key for one message of a
+    /// BLAKE3-based AEAD, given a key and a nonce.
+    ///
+    /// This hashes `nonce` with [`keyed_hash`] under `key` and splits the extended output into
+    /// 64-byte blocks, as with [`finalize_xof_at`](#method.finalize_xof_at):
+    ///
+    /// - Block 0 (output counter 0) is reserved for authentication. Its first 32 bytes are
+    ///   returned as the one-time authentication key, and the other 32 bytes are unused.
+    /// - Blocks 1 and up (output counters 1 and up) are the keystream. The returned
+    ///   [`OutputReader`] starts at position 64, the beginning of block 1.
+    ///
+    /// All the output blocks are computed with the `KEYED_HASH` and `ROOT` flags, and with the
+    /// same chunk counters as any other keyed hash of `nonce`. In other words, this is the same
+    /// as `Hasher::new_keyed(key).update(nonce).finalize_xof()`, reading the authentication key
+    /// from position 0 and the keystream from position 64. It compresses block 0 only once.
+    ///
+    /// This only provides the two pieces. Encrypting with the keystream, and authenticating the
+    /// ciphertext and any associated data with the authentication key, for example with
+    /// [`keyed_hash`], are up to the caller. **Never use the same nonce twice with the same key.**
+    /// Doing that reuses the keystream and the authentication key, which breaks both
+    /// confidentiality and authenticity.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let key = [42; 32];
+    /// let (mut keystream, auth_key) = blake3::Hasher::new_aead(&key, b"unique nonce");
+    /// let mut ciphertext = *b"hello world";
+    /// let mut pad = [0; 11];
+    /// keystream.fill(&mut pad);
+    /// for (c, p) in ciphertext.iter_mut().zip(pad) {
+    ///     *c ^= p;
+    /// }
+    /// let tag = blake3::keyed_hash(&auth_key, &ciphertext);
+    /// # let _ = tag;
+    /// ```
+    pub fn new_aead(key: &[u8; KEY_LEN], nonce: &[u8]) -> (OutputReader, [u8; KEY_LEN]) {
+        let mut hasher = Self::new_keyed(key);
+        hasher.update(nonce);
+        let (auth_key, mut keystream) = hasher.finalize_with_xof();
+        keystream.set_position(BLOCK_LEN as u64);
+        (keystream, auth_key.0)
+    }
+
     /// Construct a new `Hasher` for the key derivation function. See
     /// [`derive_key`]. The context string should be hardcoded, globally
     /// unique, and application-specific.
     ///
     /// [`derive_key`]: fn.derive_key.html
+    ///
+    /// To derive many keys with the same context string, see [`DeriveKeyContext`].
     pub fn new_derive_key(context: &str) -> Self {
-        let context_key =
-            hash_all_at_once::<join::SerialJoin>(context.as_bytes(), IV, DERIVE_KEY_CONTEXT)
-                .root_hash();
-        let context_key_words = platform::words_from_le_bytes_32(context_key.as_bytes());
-        Self::new_internal(&context_key_words, DERIVE_KEY_MATERIAL)
+        DeriveKeyContext::new(context).new_hasher()
+    }
+
+    /// Construct a new `Hasher` for the key derivation function, with a salt mixed in ahead of
+    /// the key material. See [`new_derive_key`](#method.new_derive_key).
+    ///
+    /// If `salt` is non-empty, the resulting state is the same as calling
+    /// [`update_framed`](#method.update_framed) with `salt` on a new derive-key `Hasher`. That
+    /// is, the key material is prefixed with the salt length as 8 little-endian bytes and then
+    /// the salt. If `salt` is empty, nothing is prefixed, and this is exactly
+    /// [`new_derive_key`](#method.new_derive_key).
+    ///
+    /// Because of that prefix, a salted derivation is the same as an unsalted one whose key
+    /// material happens to start with the framed salt. Don't mix salted and unsalted derivations
+    /// with the same context string.
+    pub fn new_derive_key_salted(context: &str, salt: &[u8]) -> Self {
+        let mut hasher = Self::new_derive_key(context);
+        if !salt.is_empty() {
+            hasher.update_framed(salt);
+        }
+        hasher
     }
 
     /// Reset the `Hasher` to its initial state.
@@ -1048,6 +1931,23 @@ impl Hasher {
         self
     }
 
+    /// Which of the three BLAKE3 modes this `Hasher` computes: the regular hash function
+    /// ([`new`](#method.new)), the keyed hash function ([`new_keyed`](#method.new_keyed)), or the
+    /// key derivation function ([`new_derive_key`](#method.new_derive_key)).
+    ///
+    /// Hashes from different modes are never equal for the same input, so generic code that
+    /// accepts a `Hasher` can use this to check that it got the kind it expected. The mode never
+    /// changes, including across [`reset`](#method.reset).
+    pub fn mode(&self) -> Mode {
+        if self.chunk_state.flags & KEYED_HASH != 0 {
+            Mode::Keyed
+        } else if self.chunk_state.flags & DERIVE_KEY_MATERIAL != 0 {
+            Mode::DeriveKey
+        } else {
+            Mode::Hash
+        }
+    }
+
     // As described in push_cv() below, we do "lazy merging", delaying merges
     // until right before the next CV is about to be added. This is different
     // from the reference implementation. Another difference is that we aren't
@@ -1120,13 +2020,97 @@ impl Hasher {
     ///
     /// Note that the degree of SIMD parallelism that `update` can use is limited by the size of
     /// this input buffer. See [`update_reader`](#method.update_reader).
+    #[inline]
     pub fn update(&mut self, input: &[u8]) -> &mut Self {
-        self.update_with_join::<join::SerialJoin>(input)
+        if self.chunk_state.try_append_to_buf(input) {
+            return self;
+        }
+        self.update_with_join::<join::SerialJoin>(input, usize::MAX)
+    }
+
+    /// Set the maximum number of input bytes that [`try_update`](#method.try_update) will
+    /// accept, or `None` (the default) for no limit. The limit counts all input hashed so far,
+    /// as reported by [`count`](#method.count), and it's kept across calls to
+    /// [`reset`](#method.reset).
+    ///
+    /// The limit only applies to `try_update`. Other methods like [`update`](#method.update)
+    /// ignore it.
+    pub fn set_max_input(&mut self, max_input: Option<u64>) -> &mut Self {
+        self.max_input = max_input;
+        self
+    }
+
+    /// As [`update`](#method.update), but return an error instead of adding `input`, if it would
+    /// take the total input past the limit set by [`set_max_input`](#method.set_max_input).
+    ///
+    /// This is useful for capping the amount of untrusted input a single hasher will absorb. If
+    /// this method returns an error, the hash state is unchanged, and none of `input` has been
+    /// added.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut hasher = blake3::Hasher::new();
+    /// hasher.set_max_input(Some(5));
+    /// hasher.try_update(b"foo")?;
+    /// assert!(hasher.try_update(b"bar").is_err());
+    /// hasher.try_update(b"ba")?;
+    /// assert_eq!(hasher.finalize(), blake3::hash(b"fooba"));
+    /// # Ok::<(), blake3::LimitExceeded>(())
+    /// ```
+    pub fn try_update(&mut self, input: &[u8]) -> Result<&mut Self, LimitExceeded> {
+        if let Some(max_input) = self.max_input {
+            let remaining = max_input.saturating_sub(self.count());
+            if input.len() as u64 > remaining {
+                return Err(LimitExceeded { max_input });
+            }
+        }
+        Ok(self.update(input))
+    }
+
+    /// Add a length-prefixed message to the hash state.
+    ///
+    /// This is the same as calling [`update`](#method.update) with the length of `message` as 8
+    /// little-endian bytes, and then with `message` itself. Plain `update` calls are simply
+    /// concatenated, so for example `update(b"ab"); update(b"c")` gives the same hash as
+    /// `update(b"a"); update(b"bc")`. Framing each message with its length makes a sequence of
+    /// messages unambiguous, so that different sequences never produce the same input.
+    ///
+    /// Note that framed and unframed input can still be ambiguous with each other. If you use
+    /// this method, use it for every message in the sequence.
+    pub fn update_framed(&mut self, message: &[u8]) -> &mut Self {
+        self.update(&(message.len() as u64).to_le_bytes());
+        self.update(message)
+    }
+
+    /// Add all the remaining bytes of a [`bytes::Buf`] to the hash state, advancing `buf` to the
+    /// end.
+    ///
+    /// This feeds each contiguous [`chunk`] of `buf` to [`update`](#method.update) in turn, so
+    /// non-contiguous buffers like [`Chain`] don't need to be flattened first. The result is the
+    /// same as calling `update` once with all the bytes concatenated.
+    ///
+    /// This method requires the `bytes` Cargo feature, which is disabled by default but enabled
+    /// on [docs.rs](https://docs.rs).
+    ///
+    /// [`bytes::Buf`]: https://docs.rs/bytes/1/bytes/trait.Buf.html
+    /// [`chunk`]: https://docs.rs/bytes/1/bytes/trait.Buf.html#tymethod.chunk
+    /// [`Chain`]: https://docs.rs/bytes/1/bytes/buf/struct.Chain.html
+    #[cfg(feature = "bytes")]
+    pub fn update_buf(&mut self, buf: &mut impl bytes::Buf) -> &mut Self {
+        while buf.has_remaining() {
+            let chunk = buf.chunk();
+            let len = chunk.len();
+            self.update(chunk);
+            buf.advance(len);
+        }
+        self
     }
 
-    fn update_with_join<J: join::Join>(&mut self, mut input: &[u8]) -> &mut Self {
-        // If we have some partial chunk bytes in the internal chunk_state, we
-        // need to finish that chunk first.
+    // If we have some partial chunk bytes in the internal chunk_state, we need to finish that
+    // chunk before hashing whole subtrees. Return the rest of the input. If that's empty, the
+    // chunk_state might still hold a partial or full chunk. Otherwise it's clear.
+    fn finish_partial_chunk<'a>(&mut self, mut input: &'a [u8]) -> &'a [u8] {
         if self.chunk_state.len() > 0 {
             let want = CHUNK_LEN - self.chunk_state.len();
             let take = cmp::min(want, input.len());
@@ -1135,7 +2119,7 @@ impl Hasher {
             if !input.is_empty() {
                 // We've filled the current chunk, and there's more input
                 // coming, so we know it's not the root and we can finalize it.
-                // Then we'll proceed to hashing whole chunks below.
+                // Then the caller can proceed to hashing whole chunks.
                 debug_assert_eq!(self.chunk_state.len(), CHUNK_LEN);
                 let chunk_cv = self.chunk_state.output().chaining_value();
                 self.push_cv(&chunk_cv, self.chunk_state.chunk_counter);
@@ -1145,10 +2129,48 @@ impl Hasher {
                     self.chunk_state.flags,
                     self.chunk_state.platform,
                 );
-            } else {
-                return self;
             }
         }
+        input
+    }
+
+    // The length of the largest whole subtree that the next update_with_join() iteration can hash,
+    // given `input_len` bytes of input (more than one chunk) and a clear chunk_state.
+    fn next_subtree_len(&self, input_len: usize) -> usize {
+        let mut subtree_len = largest_power_of_two_leq(input_len);
+        let count_so_far = self.chunk_state.chunk_counter * CHUNK_LEN as u64;
+        // Shrink the subtree_len until it evenly divides the count so far.
+        // We know that subtree_len itself is a power of 2, so we can use a
+        // bitmasking trick instead of an actual remainder operation. (Note
+        // that if the caller consistently passes power-of-2 inputs of the
+        // same size, as is hopefully typical, this loop condition will
+        // always fail, and subtree_len will always be the full length of
+        // the input.)
+        //
+        // An aside: We don't have to shrink subtree_len quite this much.
+        // For example, if count_so_far is 1, we could pass 2 chunks to
+        // compress_subtree_to_parent_node. Since we'll get 2 CVs back,
+        // we'll still get the right answer in the end, and we might get to
+        // use 2-way SIMD parallelism. The problem with this optimization,
+        // is that it gets us stuck always hashing 2 chunks. The total
+        // number of chunks will remain odd, and we'll never graduate to
+        // higher degrees of parallelism. See
+        // https://github.com/BLAKE3-team/BLAKE3/issues/69.
+        while (subtree_len - 1) as u64 & count_so_far != 0 {
+            subtree_len /= 2;
+        }
+        subtree_len
+    }
+
+    fn update_with_join<J: join::Join>(
+        &mut self,
+        mut input: &[u8],
+        join_depth: usize,
+    ) -> &mut Self {
+        input = self.finish_partial_chunk(input);
+        if input.is_empty() {
+            return self;
+        }
 
         // Now the chunk_state is clear, and we have more input. If there's
         // more than a single chunk (so, definitely not the root chunk), hash
@@ -1166,28 +2188,7 @@ impl Hasher {
         while input.len() > CHUNK_LEN {
             debug_assert_eq!(self.chunk_state.len(), 0, "no partial chunk data");
             debug_assert_eq!(CHUNK_LEN.count_ones(), 1, "power of 2 chunk len");
-            let mut subtree_len = largest_power_of_two_leq(input.len());
-            let count_so_far = self.chunk_state.chunk_counter * CHUNK_LEN as u64;
-            // Shrink the subtree_len until it evenly divides the count so far.
-            // We know that subtree_len itself is a power of 2, so we can use a
-            // bitmasking trick instead of an actual remainder operation. (Note
-            // that if the caller consistently passes power-of-2 inputs of the
-            // same size, as is hopefully typical, this loop condition will
-            // always fail, and subtree_len will always be the full length of
-            // the input.)
-            //
-            // An aside: We don't have to shrink subtree_len quite this much.
-            // For example, if count_so_far is 1, we could pass 2 chunks to
-            // compress_subtree_to_parent_node. Since we'll get 2 CVs back,
-            // we'll still get the right answer in the end, and we might get to
-            // use 2-way SIMD parallelism. The problem with this optimization,
-            // is that it gets us stuck always hashing 2 chunks. The total
-            // number of chunks will remain odd, and we'll never graduate to
-            // higher degrees of parallelism. See
-            // https://github.com/BLAKE3-team/BLAKE3/issues/69.
-            while (subtree_len - 1) as u64 & count_so_far != 0 {
-                subtree_len /= 2;
-            }
+            let subtree_len = self.next_subtree_len(input.len());
             // The shrunken subtree_len might now be 1 chunk long. If so, hash
             // that one chunk by itself. Otherwise, compress the subtree into a
             // pair of CVs.
@@ -1215,6 +2216,7 @@ impl Hasher {
                     self.chunk_state.chunk_counter,
                     self.chunk_state.flags,
                     self.chunk_state.platform,
+                    join_depth,
                 );
                 let left_cv = array_ref!(cv_pair, 0, 32);
                 let right_cv = array_ref!(cv_pair, 32, 32);
@@ -1318,11 +2320,198 @@ impl Hasher {
         OutputReader::new(self.final_output())
     }
 
+    /// As [`finalize_xof`](#method.finalize_xof), but starting the [`OutputReader`] at the
+    /// beginning of the given 64-byte output block.
+    ///
+    /// For `counter <= u64::MAX / 64`, this is equivalent to calling
+    /// [`set_position`](struct.OutputReader.html#method.set_position) with `counter * 64` on a
+    /// new reader. Larger counters address blocks past the 2<sup>64</sup>-1 byte maximum output
+    /// size. The reader still fills bytes from those blocks, but as with reading past the end of
+    /// the output, they're unspecified, and [`position`](struct.OutputReader.html#method.position)
+    /// saturates at `u64::MAX`. Protocols that reserve some output blocks for one purpose and the
+    /// rest for another (for example an authenticator key in block 0 and a keystream from block 1
+    /// onward) can use this to address blocks directly.
+    ///
+    /// [`OutputReader`]: struct.OutputReader.html
+    pub fn finalize_xof_at(&self, counter: u64) -> OutputReader {
+        let mut reader = OutputReader::new(self.final_output());
+        reader.inner.counter = counter;
+        reader
+    }
+
+    /// Finalize the hash state and return both the [`Hash`](struct.Hash.html)
+    /// of the input and an [`OutputReader`].
+    ///
+    /// This gives the same results as calling [`finalize`] and
+    /// [`finalize_xof`], but it only compresses the root block once. The hash
+    /// is the first 32 bytes of that block, and the reader starts at position
+    /// 0 with the whole block already computed. This is useful for the common
+    /// pattern of taking the hash and then expanding more output from it.
+    ///
+    /// [`finalize`]: #method.finalize
+    /// [`finalize_xof`]: #method.finalize_xof
+    /// [`OutputReader`]: struct.OutputReader.html
+    pub fn finalize_with_xof(&self) -> (Hash, OutputReader) {
+        let mut reader = OutputReader::new(self.final_output());
+        let block = reader.inner.root_output_block();
+        reader.block_cache = Some(block);
+        (Hash(*array_ref!(block, 0, OUT_LEN)), reader)
+    }
+
+    /// Finalize the hash state once and for all, and return a [`FinalizedHasher`], which can
+    /// supply the hash and extended output repeatedly without recomputing them.
+    ///
+    /// Unlike the other `finalize` methods, this consumes the `Hasher`, and no more input can be
+    /// added.
+    pub fn into_finalized(self) -> FinalizedHasher {
+        let output = self.final_output();
+        let root_block = output.root_output_block();
+        FinalizedHasher { output, root_block }
+    }
+
+    /// Finalize the hash state and compare the result to `expected` in constant time, as
+    /// [`ct::eq`] does. On success, return the computed [`Hash`], which is equal to `expected`.
+    /// On failure, return a [`MismatchError`] that holds both hashes.
+    ///
+    /// This is for checking a download or a received message against a hash that was published
+    /// ahead of time. Note that a plain hash can only be checked once all the input has arrived.
+    /// Detecting corruption earlier requires a verified streaming encoding like
+    /// [Bao](https://github.com/oconnor663/bao).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let expected = blake3::hash(b"foobarbaz");
+    /// let mut hasher = blake3::Hasher::new();
+    /// hasher.update(b"foo").update(b"bar").update(b"baz");
+    /// assert_eq!(hasher.finalize_and_verify(&expected), Ok(expected));
+    /// ```
+    pub fn finalize_and_verify(self, expected: &Hash) -> Result<Hash, MismatchError> {
+        let computed = self.finalize();
+        if ct::eq(&computed, expected) {
+            Ok(computed)
+        } else {
+            Err(MismatchError {
+                computed,
+                expected: *expected,
+            })
+        }
+    }
+
+    /// Finalize the hash state and return `n` bytes of extended output in a new allocation.
+    ///
+    /// This is a shortcut for allocating a buffer and filling it from
+    /// [`finalize_xof`](#method.finalize_xof). As with `finalize_xof`, the first 32 bytes are
+    /// the same as [`finalize`](#method.finalize), and shorter outputs are prefixes of longer
+    /// ones.
+    ///
+    /// This method requires the `std` Cargo feature, which is enabled by default.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut hasher = blake3::Hasher::new();
+    /// hasher.update(b"foo");
+    /// let output = hasher.expand(100);
+    /// assert_eq!(output.len(), 100);
+    /// assert_eq!(output[..32], *hasher.finalize().as_bytes());
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn expand(&self, n: usize) -> Box<[u8]> {
+        let mut output = vec![0; n].into_boxed_slice();
+        self.finalize_xof().fill(&mut output);
+        output
+    }
+
+    /// Finalize the hash state and return `N` bytes of extended output in an array.
+    ///
+    /// This is a shortcut for filling an array from [`finalize_xof`](#method.finalize_xof), and
+    /// it doesn't allocate. As with `finalize_xof`, shorter outputs are prefixes of longer ones.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut hasher = blake3::Hasher::new();
+    /// hasher.update(b"foo");
+    /// let output: [u8; 100] = hasher.finalize_array();
+    /// assert_eq!(output[..32], *hasher.finalize().as_bytes());
+    /// ```
+    pub fn finalize_array<const N: usize>(&self) -> [u8; N] {
+        let mut output = [0; N];
+        self.finalize_xof().fill(&mut output);
+        output
+    }
+
+    /// Finalize the hash state and return a [`ShortHash`] of `N` bytes, which must be between 1
+    /// and 32 inclusive. This is the first `N` bytes of [`finalize`](#method.finalize).
+    pub fn finalize_short<const N: usize>(&self) -> ShortHash<N> {
+        ShortHash::from_bytes(self.finalize_array())
+    }
+
+    /// Finalize the hash state and return both the [`Hash`](struct.Hash.html) of the input and a
+    /// 16-byte tag.
+    ///
+    /// The hash is bytes 0..32 of the extended output, and the tag is the 16 bytes that follow
+    /// it, bytes 32..48. Both come from the same root compression, so this costs the same as
+    /// [`finalize`](#method.finalize). The result is the same as reading the first 48 bytes from
+    /// [`finalize_xof`](#method.finalize_xof) and splitting them.
+    ///
+    /// Because the tag is a prefix of the extended output after the hash, anyone who knows the
+    /// input can compute it. It's only a MAC if the hasher is keyed (see
+    /// [`new_keyed`](#method.new_keyed)), and even then, publishing the hash alongside the tag
+    /// publishes output from the same key. Note that `[u8; 16]` doesn't provide constant-time
+    /// equality checking.
+    pub fn finalize_with_tag(&self) -> (Hash, [u8; 16]) {
+        let block = self.final_output().root_output_block();
+        (
+            Hash(*array_ref!(block, 0, OUT_LEN)),
+            *array_ref!(block, OUT_LEN, 16),
+        )
+    }
+
+    /// Finalize the hash state with the total input length appended, and return the
+    /// [`Hash`](struct.Hash.html) of that.
+    ///
+    /// This is the same as calling [`update`](#method.update) with [`count`](#method.count) as 8
+    /// little-endian bytes and then [`finalize`](#method.finalize), except that it doesn't modify
+    /// `self`. It's for protocols that commit to the total length at the end of the input, when
+    /// the length isn't known up front.
+    ///
+    /// The result is different from `finalize` on the same hasher. Note that it's the same as
+    /// `finalize` on a hasher that was given the length suffix explicitly, so a protocol that
+    /// uses this method should use it consistently.
+    pub fn finalize_with_length_suffix(&self) -> Hash {
+        let mut hasher = self.clone();
+        hasher.update(&self.count().to_le_bytes());
+        hasher.finalize()
+    }
+
     /// Return the total number of bytes hashed so far.
     pub fn count(&self) -> u64 {
         self.chunk_state.chunk_counter * CHUNK_LEN as u64 + self.chunk_state.len() as u64
     }
 
+    /// Undocumented and unstable, for debugging only.
+    ///
+    /// The number of subtree chaining values currently on the CV stack. This
+    /// doesn't include the chunk in progress.
+    #[doc(hidden)]
+    pub fn cv_stack_len(&self) -> usize {
+        self.cv_stack.len()
+    }
+
+    /// Undocumented and unstable, for debugging only.
+    ///
+    /// The subtree chaining values currently on the CV stack, from the left
+    /// edge of the tree to the right. Because merging is lazy, there can be
+    /// one more entry than the number of subtrees in the binary decomposition
+    /// of [`count`](#method.count). Note that for a keyed hasher, these are
+    /// secret.
+    #[doc(hidden)]
+    pub fn debug_cv_stack(&self) -> &[CVBytes] {
+        &self.cv_stack
+    }
+
     /// As [`update`](Hasher::update), but reading from a
     /// [`std::io::Read`](https://doc.rust-lang.org/std/io/trait.Read.html) implementation.
     ///
@@ -1367,6 +2556,127 @@ impl Hasher {
         Ok(self)
     }
 
+    /// As [`update_reader`](Hasher::update_reader), but giving up once `deadline` has passed.
+    ///
+    /// This is for readers that might stall, like a network stream from a peer who sends data
+    /// very slowly or not at all. If the reader reaches EOF before the deadline, this is the same
+    /// as `update_reader`. Otherwise it returns an error of kind
+    /// [`TimedOut`](std::io::ErrorKind::TimedOut). In that case, the `Hasher` has already
+    /// received all the bytes that were read, so it's in a consistent state. It can be finalized
+    /// over that partial input, or updated with more input later.
+    ///
+    /// The deadline is checked between reads, so this can't interrupt a single read that blocks
+    /// forever. Give the reader a read timeout of its own, for example with
+    /// [`TcpStream::set_read_timeout`](std::net::TcpStream::set_read_timeout), so that reads
+    /// return regularly. Errors of kind `TimedOut` or `WouldBlock` from the reader are retried
+    /// until the deadline, so both per-read timeouts and non-blocking readers work. Other errors
+    /// are returned as in `update_reader`.
+    ///
+    /// This method requires the `std` Cargo feature, which is enabled by default.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use std::net::TcpStream;
+    /// # use std::time::{Duration, Instant};
+    /// # fn main() -> std::io::Result<()> {
+    /// let stream = TcpStream::connect("example.com:1234")?;
+    /// stream.set_read_timeout(Some(Duration::from_secs(1)))?;
+    /// let mut hasher = blake3::Hasher::new();
+    /// hasher.update_reader_timeout(&stream, Instant::now() + Duration::from_secs(30))?;
+    /// println!("{}", hasher.finalize());
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn update_reader_timeout(
+        &mut self,
+        reader: impl std::io::Read,
+        deadline: std::time::Instant,
+    ) -> std::io::Result<&mut Self> {
+        io::copy_until(reader, self, deadline)?;
+        Ok(self)
+    }
+
+    /// As [`update_reader`](Hasher::update_reader), but reading into a buffer provided by the
+    /// caller.
+    ///
+    /// `update_reader` uses a fixed-size buffer on the stack. This method gives the caller
+    /// control over the size and location of the buffer instead, for example to use a smaller
+    /// one where stack space is tight, or to reuse one across many readers. Each fill of
+    /// `scratch` is passed to [`update`](Hasher::update), so the same advice about SIMD
+    /// parallelism applies: buffers smaller than 16 KiB can limit performance.
+    ///
+    /// This method requires the `std` Cargo feature, which is enabled by default.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `scratch` is empty.
+    #[cfg(feature = "std")]
+    pub fn update_reader_buf(
+        &mut self,
+        reader: impl std::io::Read,
+        scratch: &mut [u8],
+    ) -> std::io::Result<&mut Self> {
+        io::copy_with_buf(reader, self, scratch)?;
+        Ok(self)
+    }
+
+    /// As [`update_reader`](Hasher::update_reader), but taking a trait object.
+    ///
+    /// `&mut dyn Read` already implements `Read`, so `update_reader` accepts it too. This method
+    /// is for code that has erased the reader type and wants a non-generic entry point, for
+    /// example to store it as a function pointer. The results are identical.
+    ///
+    /// This method requires the `std` Cargo feature, which is enabled by default.
+    #[cfg(feature = "std")]
+    pub fn update_dyn_reader(
+        &mut self,
+        reader: &mut dyn std::io::Read,
+    ) -> std::io::Result<&mut Self> {
+        self.update_reader(reader)
+    }
+
+    /// As [`update_reader`](Hasher::update_reader), but committing to the length of the file
+    /// before its contents.
+    ///
+    /// The input layout is an 8-byte tag, the ASCII bytes `filelen` followed by `0xff`, then the
+    /// file length from its metadata as 8 little-endian bytes, then exactly that many bytes of
+    /// contents. That is, the result is the same as calling
+    /// [`update`](Hasher::update) with the tag, then with `len.to_le_bytes()`, and then with the
+    /// contents. Because the length comes first, a file can't collide with a truncated copy of
+    /// itself that has other input appended after it.
+    ///
+    /// The tag keeps this layout distinct from [`update_framed`](Hasher::update_framed), which
+    /// writes a plain length prefix. Read as a little-endian length, the tag would be larger than
+    /// any slice can be, so `update_framed` never produces it.
+    ///
+    /// The whole file is hashed, so `file` must be at its start. If its seek position isn't 0,
+    /// this method returns an error of kind [`InvalidInput`](std::io::ErrorKind::InvalidInput)
+    /// without hashing anything. If the file shrinks while it's being read, this method returns an
+    /// error of kind [`UnexpectedEof`](std::io::ErrorKind::UnexpectedEof), and if it grows, an
+    /// error of kind [`InvalidData`](std::io::ErrorKind::InvalidData). Otherwise, the length
+    /// prefix always matches the contents.
+    ///
+    /// This method requires the `std` Cargo feature, which is enabled by default.
+    #[cfg(feature = "std")]
+    pub fn update_file_with_len(&mut self, file: &std::fs::File) -> std::io::Result<&mut Self> {
+        use std::io::Seek;
+        // Seek is implemented for &File, and this doesn't move the position.
+        let mut file_ref = file;
+        if file_ref.stream_position()? != 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "file is not at its start",
+            ));
+        }
+        let len = file.metadata()?.len();
+        self.update(FILE_WITH_LEN_TAG);
+        self.update(&len.to_le_bytes());
+        io::copy_exact(file, self, len)?;
+        Ok(self)
+    }
+
     /// As [`update`](Hasher::update), but using Rayon-based multithreading
     /// internally.
     ///
@@ -1387,9 +2697,85 @@ impl Hasher {
     /// reads can be slow. For hashing whole files, see
     /// [`update_mmap_rayon`](Hasher::update_mmap_rayon), which is gated by both
     /// the `rayon` and `mmap` Cargo features.
+    ///
+    /// The result is always exactly the same as [`update`](Hasher::update) with the same input.
+    /// The shape of the BLAKE3 tree depends only on the input length, so the number of threads
+    /// and the order in which they finish can't affect the output.
+    ///
+    /// Rayon tasks are only spawned down to a limited depth of the tree, which by default is
+    /// enough to give each thread in the current pool a few subtrees to work on. Below that depth,
+    /// subtrees are hashed serially. To choose a different limit, see
+    /// [`update_rayon_with_max_depth`](Hasher::update_rayon_with_max_depth).
     #[cfg(feature = "rayon")]
     pub fn update_rayon(&mut self, input: &[u8]) -> &mut Self {
-        self.update_with_join::<join::RayonJoin>(input)
+        self.update_rayon_with_max_depth(input, join::default_max_depth())
+    }
+
+    /// As [`update_rayon`](Hasher::update_rayon), but with an explicit limit on the depth of
+    /// parallel recursion.
+    ///
+    /// Each level of recursion splits a subtree in half and hashes the halves with
+    /// [`rayon::join`], so a limit of `max_depth` produces at most `2^max_depth` Rayon tasks per
+    /// subtree. Below that depth, subtrees are hashed serially on the thread that reached them.
+    /// A limit of 0 hashes everything on the calling thread, and `usize::MAX` removes the limit
+    /// entirely. The limit doesn't affect the output, only performance.
+    ///
+    /// This method is gated by the `rayon` Cargo feature, which is disabled by default.
+    ///
+    /// [`rayon::join`]: https://docs.rs/rayon/latest/rayon/fn.join.html
+    #[cfg(feature = "rayon")]
+    pub fn update_rayon_with_max_depth(&mut self, input: &[u8], max_depth: usize) -> &mut Self {
+        self.update_with_join::<join::RayonJoin>(input, max_depth)
+    }
+
+    /// As [`update_rayon`](Hasher::update_rayon), but running on the given Rayon thread pool
+    /// rather than the current one (usually the global pool). The default depth limit is based
+    /// on the size of `pool`.
+    ///
+    /// As with `update_rayon`, the result doesn't depend on the number of threads in the pool.
+    ///
+    /// This method is gated by the `rayon` Cargo feature, which is disabled by default.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # #[cfg(feature = "rayon")]
+    /// # {
+    /// let pool = rayon::ThreadPoolBuilder::new().num_threads(4).build()?;
+    /// let input = vec![0; 1 << 20];
+    /// let mut hasher = blake3::Hasher::new();
+    /// hasher.update_rayon_in(&input, &pool);
+    /// assert_eq!(hasher.finalize(), blake3::hash(&input));
+    /// # }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn update_rayon_in(&mut self, input: &[u8], pool: &rayon::ThreadPool) -> &mut Self {
+        pool.install(|| self.update_rayon(input))
+    }
+
+    /// As [`update_rayon`](Hasher::update_rayon), but keeping memory accesses local on machines
+    /// with more than one NUMA node.
+    ///
+    /// This method is gated by the `numa` Cargo feature, which is disabled by default, and it's
+    /// only available on Linux.
+    ///
+    /// On a multi-socket machine, reading memory that belongs to another socket is slower, and
+    /// with `update_rayon` about half of the reads of a large input cross sockets. This method
+    /// finds which node owns each part of `input`, and hashes large subtrees (at least 8 MiB) on
+    /// a separate thread pool for that node, whose threads are pinned to that node's CPUs. The
+    /// per-node pools are created on the first call. The result is always exactly the same as
+    /// [`update`](Hasher::update) with the same input.
+    ///
+    /// Only pages that are already in memory have an owner. Parts of a fresh memory map that
+    /// haven't been touched yet are hashed on the global Rayon pool, as with `update_rayon`. On a
+    /// machine with a single NUMA node, or if the topology can't be read from `/sys`, this method
+    /// is equivalent to `update_rayon`.
+    #[cfg(all(feature = "numa", target_os = "linux"))]
+    pub fn update_rayon_numa(&mut self, input: &[u8]) -> &mut Self {
+        numa::update(self, input, numa::Topology::system())
     }
 
     /// As [`update`](Hasher::update), but reading the contents of a file using memory mapping.
@@ -1437,7 +2823,7 @@ impl Hasher {
     pub fn update_mmap(&mut self, path: impl AsRef<std::path::Path>) -> std::io::Result<&mut Self> {
         let file = std::fs::File::open(path.as_ref())?;
         if let Some(mmap) = io::maybe_mmap_file(&file)? {
-            self.update(&mmap);
+            self.update_mmap_ref(&mmap);
         } else {
             io::copy_wide(&file, self)?;
         }
@@ -1492,12 +2878,208 @@ impl Hasher {
     ) -> std::io::Result<&mut Self> {
         let file = std::fs::File::open(path.as_ref())?;
         if let Some(mmap) = io::maybe_mmap_file(&file)? {
-            self.update_rayon(&mmap);
+            self.update_mmap_ref_rayon(&mmap);
         } else {
             io::copy_wide(&file, self)?;
         }
         Ok(self)
     }
+
+    /// As [`update`](Hasher::update), for a memory map that the caller has already created.
+    ///
+    /// This is exactly the same as `update`. It exists to make the pattern discoverable: a
+    /// [`memmap2::Mmap`](https://docs.rs/memmap2/latest/memmap2/struct.Mmap.html) dereferences to
+    /// `&[u8]`, so it can be passed here (or to `update`) directly. Use this when you need control
+    /// over how the file is mapped, for example with your own `madvise` hints or a mapping that's
+    /// reused for other work. Otherwise [`update_mmap`](Hasher::update_mmap) handles opening and
+    /// mapping the file for you, including falling back to regular reads for small or virtual
+    /// files.
+    ///
+    /// Note that hashing a memory-mapped file while another process is modifying it gives
+    /// unpredictable results. Making sure that doesn't happen is up to the caller.
+    ///
+    /// This method requires the `mmap` Cargo feature, which is disabled by default but enabled on
+    /// [docs.rs](https://docs.rs).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> std::io::Result<()> {
+    /// let file = std::fs::File::open("file.dat")?;
+    /// let mmap = unsafe { memmap2::Mmap::map(&file)? };
+    /// let mut hasher = blake3::Hasher::new();
+    /// hasher.update_mmap_ref(&mmap);
+    /// println!("{}", hasher.finalize());
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "mmap")]
+    pub fn update_mmap_ref(&mut self, mmap: &[u8]) -> &mut Self {
+        self.update(mmap)
+    }
+
+    /// As [`update_rayon`](Hasher::update_rayon), for a memory map that the caller has already
+    /// created. See [`update_mmap_ref`](Hasher::update_mmap_ref), and the performance warning
+    /// associated with [`update_mmap_rayon`](Hasher::update_mmap_rayon).
+    ///
+    /// This method requires both the `mmap` and `rayon` Cargo features, which are disabled by
+    /// default but enabled on [docs.rs](https://docs.rs).
+    #[cfg(feature = "mmap")]
+    #[cfg(feature = "rayon")]
+    pub fn update_mmap_ref_rayon(&mut self, mmap: &[u8]) -> &mut Self {
+        self.update_rayon(mmap)
+    }
+
+    /// Return an adapter that implements [`fmt::Write`], so that formatted text can be hashed
+    /// without allocating a `String`. Each piece of the formatted output is added to the hasher as
+    /// UTF-8 bytes as soon as it's produced.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use core::fmt::Write;
+    ///
+    /// let mut hasher = blake3::Hasher::new();
+    /// write!(hasher.fmt(), "{}-{}", 1, 2).unwrap();
+    /// assert_eq!(hasher.finalize(), blake3::hash(b"1-2"));
+    /// ```
+    pub fn fmt(&mut self) -> HasherFmt<'_> {
+        HasherFmt { hasher: self }
+    }
+}
+
+/// The error type for [`Hasher::try_update`], returned when the input would exceed the limit set
+/// by [`Hasher::set_max_input`].
+#[derive(Clone, Debug)]
+pub struct LimitExceeded {
+    max_input: u64,
+}
+
+impl LimitExceeded {
+    /// The limit that would have been exceeded.
+    pub fn max_input(&self) -> u64 {
+        self.max_input
+    }
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "input exceeds the limit of {} bytes", self.max_input)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LimitExceeded {}
+
+/// The error type for [`Hasher::finalize_and_verify`], returned when the computed hash doesn't
+/// match the expected one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MismatchError {
+    computed: Hash,
+    expected: Hash,
+}
+
+impl MismatchError {
+    /// The hash of the input that was actually received.
+    pub fn computed(&self) -> &Hash {
+        &self.computed
+    }
+
+    /// The hash that the input was expected to have.
+    pub fn expected(&self) -> &Hash {
+        &self.expected
+    }
+}
+
+impl fmt::Display for MismatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "hash mismatch: expected {}, computed {}",
+            self.expected, self.computed
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MismatchError {}
+
+/// The finished state of a [`Hasher`], returned by [`Hasher::into_finalized`].
+///
+/// The root block is compressed once, when the `FinalizedHasher` is created. After that,
+/// [`hash`](#method.hash) is a copy, and [`xof`](#method.xof) returns an [`OutputReader`] with
+/// its first block already computed. `FinalizedHasher` is immutable, so it can be shared across
+/// threads behind a `&` reference or an `Arc`.
+///
+/// # Example
+///
+/// ```
+/// let mut hasher = blake3::Hasher::new();
+/// hasher.update(b"foo");
+/// let finalized = hasher.into_finalized();
+/// assert_eq!(finalized.hash(), blake3::hash(b"foo"));
+/// let mut output = [0; 100];
+/// finalized.xof().fill(&mut output);
+/// assert_eq!(output[..32], *blake3::hash(b"foo").as_bytes());
+/// ```
+#[cfg_attr(feature = "zeroize", derive(zeroize::Zeroize))]
+#[derive(Clone)]
+pub struct FinalizedHasher {
+    output: Output,
+    // The first 64 bytes of output. The hash is the first half.
+    root_block: [u8; BLOCK_LEN],
+}
+
+impl FinalizedHasher {
+    /// The [`Hash`] of the input, the same as [`Hasher::finalize`] would have returned.
+    #[inline]
+    pub fn hash(&self) -> Hash {
+        Hash(*array_ref!(self.root_block, 0, OUT_LEN))
+    }
+
+    /// An [`OutputReader`] starting at position 0, the same as [`Hasher::finalize_xof`] would
+    /// have returned.
+    pub fn xof(&self) -> OutputReader {
+        let mut reader = OutputReader::new(self.output.clone());
+        reader.block_cache = Some(self.root_block);
+        reader
+    }
+}
+
+// Don't derive(Debug), because the state may be secret.
+impl fmt::Debug for FinalizedHasher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FinalizedHasher")
+            .field("flags", &self.output.flags)
+            .field("platform", &self.output.platform)
+            .finish()
+    }
+}
+
+/// A [`fmt::Write`] adapter for a [`Hasher`], returned by [`Hasher::fmt`].
+#[derive(Debug)]
+pub struct HasherFmt<'a> {
+    hasher: &'a mut Hasher,
+}
+
+impl fmt::Write for HasherFmt<'_> {
+    #[inline]
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.hasher.update(s.as_bytes());
+        Ok(())
+    }
+}
+
+/// The mode of a [`Hasher`], returned by [`Hasher::mode`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Mode {
+    /// The regular hash function, as in [`hash`] and [`Hasher::new`].
+    Hash,
+    /// The keyed hash function, as in [`keyed_hash`] and [`Hasher::new_keyed`].
+    Keyed,
+    /// The key derivation function, as in [`derive_key`] and [`Hasher::new_derive_key`].
+    DeriveKey,
 }
 
 // Don't derive(Debug), because the state may be secret.
@@ -1532,6 +3114,87 @@ impl std::io::Write for Hasher {
     }
 }
 
+/// A [`Hasher`] that can't be sent or shared between threads.
+///
+/// `Hasher` is `Send` and `Sync`, like most plain data. Some FFI code hands out pointers to a
+/// hasher that must only be used on the thread that created it, and wrapping the hasher in a
+/// `LocalHasher` lets the compiler enforce that on the Rust side. Otherwise it behaves exactly
+/// like the `Hasher` it wraps, and it has the same size and layout.
+///
+/// ```compile_fail
+/// fn assert_send<T: Send>() {}
+/// assert_send::<blake3::LocalHasher>();
+/// ```
+///
+/// ```compile_fail
+/// fn assert_sync<T: Sync>() {}
+/// assert_sync::<blake3::LocalHasher>();
+/// ```
+///
+/// # Example
+///
+/// ```
+/// let mut hasher = blake3::LocalHasher::new();
+/// hasher.update(b"foo");
+/// assert_eq!(hasher.finalize(), blake3::hash(b"foo"));
+/// ```
+#[derive(Clone, Debug, Default)]
+#[repr(transparent)]
+pub struct LocalHasher {
+    inner: Hasher,
+    // Raw pointers are neither Send nor Sync.
+    _not_send: core::marker::PhantomData<*const ()>,
+}
+
+impl LocalHasher {
+    /// Construct a new `LocalHasher` for the regular hash function. See [`Hasher::new`].
+    pub fn new() -> Self {
+        Self::from(Hasher::new())
+    }
+
+    /// Unwrap the inner [`Hasher`], which can be sent to other threads again.
+    pub fn into_inner(self) -> Hasher {
+        self.inner
+    }
+
+    /// See [`Hasher::update`].
+    #[inline]
+    pub fn update(&mut self, input: &[u8]) -> &mut Self {
+        self.inner.update(input);
+        self
+    }
+
+    /// See [`Hasher::finalize`].
+    #[inline]
+    pub fn finalize(&self) -> Hash {
+        self.inner.finalize()
+    }
+
+    /// See [`Hasher::finalize_xof`].
+    #[inline]
+    pub fn finalize_xof(&self) -> OutputReader {
+        self.inner.finalize_xof()
+    }
+
+    /// See [`Hasher::reset`].
+    #[inline]
+    pub fn reset(&mut self) -> &mut Self {
+        self.inner.reset();
+        self
+    }
+}
+
+impl From<Hasher> for LocalHasher {
+    /// Wrap a `Hasher` in any mode, including any input it's already received.
+    #[inline]
+    fn from(inner: Hasher) -> Self {
+        Self {
+            inner,
+            _not_send: core::marker::PhantomData,
+        }
+    }
+}
+
 /// An incremental reader for extended output, returned by
 /// [`Hasher::finalize_xof`](struct.Hasher.html#method.finalize_xof).
 ///
@@ -1560,6 +3223,8 @@ impl std::io::Write for Hasher {
 pub struct OutputReader {
     inner: Output,
     position_within_block: u8,
+    // The output block at the current counter, if we've already computed it.
+    block_cache: Option<[u8; BLOCK_LEN]>,
 }
 
 impl OutputReader {
@@ -1567,6 +3232,7 @@ impl OutputReader {
         Self {
             inner,
             position_within_block: 0,
+            block_cache: None,
         }
     }
 
@@ -1574,32 +3240,71 @@ impl OutputReader {
     /// `OutputReader`. This is equivalent to [`Read::read`], except that it
     /// doesn't return a `Result`. Both methods always fill the entire buffer.
     ///
-    /// Note that `OutputReader` doesn't buffer output bytes internally, so
-    /// calling `fill` repeatedly with a short-length or odd-length slice will
-    /// end up performing the same compression multiple times. If you're
-    /// reading output in a loop, prefer a slice length that's a multiple of
-    /// 64.
+    /// `OutputReader` keeps the current 64-byte output block internally, so
+    /// calling `fill` repeatedly with short slices won't repeat the
+    /// compression for bytes within the same block. However, if you're reading
+    /// output in a loop, prefer a slice length that's a multiple of 64, to
+    /// avoid the extra copying.
     ///
     /// The maximum output size of BLAKE3 is 2<sup>64</sup>-1 bytes. If you try
     /// to extract more than that, for example by seeking near the end and
-    /// reading further, the behavior is unspecified.
+    /// reading further, the output bytes are unspecified. However, this never
+    /// panics, and [`position`](#method.position) saturates at `u64::MAX`.
+    ///
+    /// Filling an empty buffer is a no-op. It doesn't move the position or
+    /// compute any output.
     ///
     /// [`Read::read`]: #method.read
     pub fn fill(&mut self, mut buf: &mut [u8]) {
         while !buf.is_empty() {
-            let block: [u8; BLOCK_LEN] = self.inner.root_output_block();
+            let block: [u8; BLOCK_LEN] = match self.block_cache {
+                Some(block) => block,
+                None => self.inner.root_output_block(),
+            };
             let output_bytes = &block[self.position_within_block as usize..];
             let take = cmp::min(buf.len(), output_bytes.len());
             buf[..take].copy_from_slice(&output_bytes[..take]);
             buf = &mut buf[take..];
             self.position_within_block += take as u8;
             if self.position_within_block == BLOCK_LEN as u8 {
-                self.inner.counter += 1;
+                self.inner.counter = self.inner.counter.wrapping_add(1);
                 self.position_within_block = 0;
+                self.block_cache = None;
+            } else {
+                self.block_cache = Some(block);
             }
         }
     }
 
+    /// As [`fill`](#method.fill), but using Rayon-based multithreading internally.
+    ///
+    /// This method is gated by the `rayon` Cargo feature, which is disabled by default but
+    /// enabled on [docs.rs](https://docs.rs).
+    ///
+    /// Each 64-byte output block depends only on its own position, so `buf` is split into
+    /// block-aligned spans that are filled in parallel. The result is always exactly the same as
+    /// [`fill`](#method.fill), and the reader's position moves forward by `buf.len()` in the
+    /// same way. To get any performance benefit from multithreading, `buf` needs to be large,
+    /// typically hundreds of KiB or more. For smaller outputs, prefer `fill`.
+    #[cfg(feature = "rayon")]
+    pub fn fill_rayon(&mut self, buf: &mut [u8]) {
+        // Fill up to the next block boundary serially.
+        let head_len = cmp::min(
+            buf.len(),
+            (BLOCK_LEN - self.position_within_block as usize) % BLOCK_LEN,
+        );
+        let (head, rest) = buf.split_at_mut(head_len);
+        self.fill(head);
+        let (blocks, tail) = rest.split_at_mut(rest.len() - rest.len() % BLOCK_LEN);
+        if !blocks.is_empty() {
+            fill_blocks_with_join::<join::RayonJoin>(&self.inner, self.inner.counter, blocks);
+            let num_blocks = (blocks.len() / BLOCK_LEN) as u64;
+            self.inner.counter = self.inner.counter.wrapping_add(num_blocks);
+            self.block_cache = None;
+        }
+        self.fill(tail);
+    }
+
     /// Return the current read position in the output stream. This is
     /// equivalent to [`Seek::stream_position`], except that it doesn't return
     /// a `Result`. The position of a new `OutputReader` starts at 0, and each
@@ -1610,19 +3315,86 @@ impl OutputReader {
     /// [`fill`]: #method.fill
     /// [`Read::read`]: #method.read
     pub fn position(&self) -> u64 {
-        self.inner.counter * BLOCK_LEN as u64 + self.position_within_block as u64
+        // Reading the last byte of a maximum-length output moves the block
+        // counter to 2^58, one past the last block that u64 positions can
+        // address. Saturate rather than overflow in that case.
+        self.inner
+            .counter
+            .saturating_mul(BLOCK_LEN as u64)
+            .saturating_add(self.position_within_block as u64)
     }
 
     /// Seek to a new read position in the output stream. This is equivalent to
     /// calling [`Seek::seek`] with [`SeekFrom::Start`], except that it doesn't
     /// return a `Result`.
     ///
+    /// Every `u64` is a valid position, so the maximum seekable byte offset is
+    /// `u64::MAX`, the last byte of the 2<sup>64</sup>-1 byte output. The
+    /// block counter of that position is `u64::MAX / 64`, which can't
+    /// overflow.
+    ///
     /// [`Seek::seek`]: #method.seek
     /// [`SeekFrom::Start`]: https://doc.rust-lang.org/std/io/enum.SeekFrom.html
     pub fn set_position(&mut self, position: u64) {
+        let counter = position / BLOCK_LEN as u64;
+        if counter != self.inner.counter {
+            self.block_cache = None;
+        }
         self.position_within_block = (position % BLOCK_LEN as u64) as u8;
-        self.inner.counter = position / BLOCK_LEN as u64;
+        self.inner.counter = counter;
     }
+
+    /// Serialize the state behind this `OutputReader`, so that the same output stream can be
+    /// read later or somewhere else with [`from_output_bytes`](#method.from_output_bytes).
+    ///
+    /// The result is the finalized root node: its input chaining value as little-endian words,
+    /// its 64-byte block, the length of the block, and its domain flags, for a total of
+    /// [`OUTPUT_BYTES_LEN`] bytes. It doesn't include the read position, so every reader of the
+    /// same output serializes the same way. The format depends only on the BLAKE3 spec and not
+    /// on the platform.
+    ///
+    /// Anyone with these bytes can compute the entire output stream, so they're as sensitive as
+    /// the output itself. With [`keyed_hash`] or [`derive_key`], they don't reveal the key, but
+    /// they do reveal every output byte.
+    pub fn to_output_bytes(&self) -> [u8; OUTPUT_BYTES_LEN] {
+        self.inner.to_bytes()
+    }
+
+    /// Reconstruct an `OutputReader` from the bytes returned by
+    /// [`to_output_bytes`](#method.to_output_bytes), starting at `position`.
+    ///
+    /// The new reader produces the same bytes as the original one did at the same position.
+    /// This returns an error if `bytes` isn't [`OUTPUT_BYTES_LEN`] bytes long, or if the block
+    /// length or flags couldn't have come from `to_output_bytes`.
+    pub fn from_output_bytes(bytes: &[u8], position: u64) -> Result<Self, OutputBytesError> {
+        let mut reader = Self::new(Output::from_bytes(bytes, 0)?);
+        reader.set_position(position);
+        Ok(reader)
+    }
+}
+
+// Fill a block-aligned buffer with output blocks starting at `counter`. Spans at or below
+// FILL_SERIAL_LEN are filled serially, and larger spans are split in half at a block boundary.
+#[cfg(feature = "rayon")]
+fn fill_blocks_with_join<J: join::Join>(output: &Output, counter: u64, buf: &mut [u8]) {
+    const FILL_SERIAL_LEN: usize = 256 * BLOCK_LEN;
+    debug_assert_eq!(buf.len() % BLOCK_LEN, 0);
+    if buf.len() <= FILL_SERIAL_LEN {
+        let mut output = output.clone();
+        output.counter = counter;
+        for block in buf.chunks_exact_mut(BLOCK_LEN) {
+            block.copy_from_slice(&output.root_output_block());
+            output.counter = output.counter.wrapping_add(1);
+        }
+        return;
+    }
+    let left_len = buf.len() / BLOCK_LEN / 2 * BLOCK_LEN;
+    let right_counter = counter.wrapping_add((left_len / BLOCK_LEN) as u64);
+    let (left, right) = buf.split_at_mut(left_len);
+    J::join(
+        || fill_blocks_with_join::<J>(output, counter, left),
+        || fill_blocks_with_join::<J>(output, right_counter, right),
+    );
 }
 
 // Don't derive(Debug), because the state may be secret.
@@ -1643,13 +3415,101 @@ impl std::io::Read for OutputReader {
     }
 }
 
+/// A seek position for [`XofSeek::seek_xof`]. This mirrors the `Start` and `Current` variants
+/// of [`std::io::SeekFrom`], which isn't available without the `std` Cargo feature. There's no
+/// `End` variant, because an extended output doesn't have a meaningful end.
+///
+/// [`std::io::SeekFrom`]: https://doc.rust-lang.org/std/io/enum.SeekFrom.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum XofSeekFrom {
+    /// Seek to the given number of bytes from the start of the output.
+    Start(u64),
+    /// Seek forward or backward from the current position by the given number of bytes.
+    Current(i64),
+}
+
+/// The error type for [`XofSeek::seek_xof`].
+///
+/// The `.to_string()` representation of this error isn't a stable API detail, and it may change
+/// at any time.
+#[derive(Clone, Debug)]
+pub struct XofSeekError(XofSeekErrorInner);
+
+#[derive(Clone, Debug)]
+enum XofSeekErrorInner {
+    BeforeStart,
+}
+
+impl fmt::Display for XofSeekError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            XofSeekErrorInner::BeforeStart => write!(f, "seek before start"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for XofSeekError {}
+
+/// Seeking within an extended output stream, without depending on `std`.
+///
+/// This is the `no_std` counterpart to [`std::io::Seek`], which [`OutputReader`] also implements
+/// when the `std` Cargo feature is enabled. Positions past `u64::MAX` saturate, and positions
+/// before the start of the output are an error. The method is named `seek_xof` rather than
+/// `seek`, so that calls aren't ambiguous when both traits are in scope.
+///
+/// [`std::io::Seek`]: https://doc.rust-lang.org/std/io/trait.Seek.html
+///
+/// # Example
+///
+/// ```
+/// use blake3::{XofSeek, XofSeekFrom};
+///
+/// let mut reader = blake3::Hasher::new().update(b"foo").finalize_xof();
+/// assert_eq!(reader.seek_xof(XofSeekFrom::Start(100)).unwrap(), 100);
+/// assert_eq!(reader.seek_xof(XofSeekFrom::Current(-10)).unwrap(), 90);
+/// assert_eq!(reader.position(), 90);
+/// assert!(reader.seek_xof(XofSeekFrom::Current(-100)).is_err());
+/// ```
+pub trait XofSeek {
+    /// Seek to a new position in the output stream, and return the new position.
+    fn seek_xof(&mut self, pos: XofSeekFrom) -> Result<u64, XofSeekError>;
+
+    /// Return the current position in the output stream.
+    fn position(&self) -> u64;
+}
+
+impl XofSeek for OutputReader {
+    fn seek_xof(&mut self, pos: XofSeekFrom) -> Result<u64, XofSeekError> {
+        let target_position = match pos {
+            XofSeekFrom::Start(x) => x,
+            XofSeekFrom::Current(x) => {
+                let current = OutputReader::position(self);
+                if x >= 0 {
+                    current.saturating_add(x as u64)
+                } else {
+                    current
+                        .checked_sub(x.unsigned_abs())
+                        .ok_or(XofSeekError(XofSeekErrorInner::BeforeStart))?
+                }
+            }
+        };
+        self.set_position(target_position);
+        Ok(target_position)
+    }
+
+    #[inline]
+    fn position(&self) -> u64 {
+        OutputReader::position(self)
+    }
+}
+
 #[cfg(feature = "std")]
 impl std::io::Seek for OutputReader {
     fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
-        let max_position = u64::max_value() as i128;
-        let target_position: i128 = match pos {
-            std::io::SeekFrom::Start(x) => x as i128,
-            std::io::SeekFrom::Current(x) => self.position() as i128 + x as i128,
+        let pos = match pos {
+            std::io::SeekFrom::Start(x) => XofSeekFrom::Start(x),
+            std::io::SeekFrom::Current(x) => XofSeekFrom::Current(x),
             std::io::SeekFrom::End(_) => {
                 return Err(std::io::Error::new(
                     std::io::ErrorKind::InvalidInput,
@@ -1657,13 +3517,7 @@ impl std::io::Seek for OutputReader {
                 ));
             }
         };
-        if target_position < 0 {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidInput,
-                "seek before start",
-            ));
-        }
-        self.set_position(cmp::min(target_position, max_position) as u64);
-        Ok(self.position())
+        self.seek_xof(pos)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))
     }
 }
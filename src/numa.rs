@@ -0,0 +1,302 @@
+//! NUMA-aware multithreading for
+//! [`Hasher::update_rayon_numa`](crate::Hasher::update_rayon_numa). Linux only.
+//!
+//! On a multi-socket machine, each NUMA node has its own memory, and reading another node's
+//! memory is slower. This module keeps one Rayon thread pool per node, with its threads pinned to
+//! that node's CPUs, and hashes each large subtree on the pool of the node that owns its memory.
+//! Ownership comes from the `move_pages` syscall in query mode, which doesn't move anything.
+//! Everything here is best-effort: if the topology can't be read, or if there's only one node,
+//! hashing falls back to the global Rayon pool.
+
+use crate::{join, parent_node_output, CVBytes, CVWords, Hasher, Platform, CHUNK_LEN};
+use arrayref::array_ref;
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::Once;
+
+// Subtrees are split until each one lives on a single node, but not below this size. Smaller
+// subtrees aren't worth a trip to another pool.
+const MIN_SUBTREE_LEN: usize = 4 << 20;
+
+pub(crate) struct Topology {
+    // Indexed by node number. Nodes without a usable pool are None.
+    pools: Vec<Option<rayon::ThreadPool>>,
+    node_of: fn(*const u8) -> Option<usize>,
+    min_subtree_len: usize,
+}
+
+impl Topology {
+    pub(crate) fn system() -> &'static Self {
+        // OnceLock would be simpler, but it needs Rust 1.70, which is newer than our MSRV. The
+        // topology is detected once and then leaked, so it lives for the rest of the process.
+        static INIT: Once = Once::new();
+        static TOPOLOGY: AtomicPtr<Topology> = AtomicPtr::new(core::ptr::null_mut());
+        INIT.call_once(|| {
+            TOPOLOGY.store(Box::into_raw(Box::new(Self::detect())), Ordering::Release);
+        });
+        // SAFETY: call_once has returned, so the pointer is set, and it's never freed or changed.
+        unsafe { &*TOPOLOGY.load(Ordering::Acquire) }
+    }
+
+    fn detect() -> Self {
+        let mut pools = Vec::new();
+        let nodes = read_list("/sys/devices/system/node/online").unwrap_or_default();
+        // Don't spawn any threads unless there's more than one node to choose from.
+        if nodes.len() > 1 {
+            for node in nodes {
+                let path = format!("/sys/devices/system/node/node{}/cpulist", node);
+                let pool = read_list(&path).and_then(|cpus| pinned_pool(node, cpus));
+                if pools.len() <= node {
+                    pools.resize_with(node + 1, || None);
+                }
+                pools[node] = pool;
+            }
+        }
+        Self {
+            pools,
+            node_of: page_node,
+            min_subtree_len: MIN_SUBTREE_LEN,
+        }
+    }
+
+    fn num_pools(&self) -> usize {
+        self.pools.iter().filter(|pool| pool.is_some()).count()
+    }
+
+    // The pool for the node that owns both the first and the last byte of `input`, if any.
+    fn pool_for(&self, input: &[u8]) -> Option<&rayon::ThreadPool> {
+        let first = (self.node_of)(input.first()?)?;
+        let last = (self.node_of)(input.last()?)?;
+        if first != last {
+            return None;
+        }
+        self.pools.get(first)?.as_ref()
+    }
+}
+
+// Parse a sysfs list like "0-3,8,10-11".
+fn read_list(path: &str) -> Option<Vec<usize>> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    let mut list = Vec::new();
+    for range in contents.trim().split(',').filter(|range| !range.is_empty()) {
+        match range.split_once('-') {
+            Some((start, end)) => list.extend(start.parse::<usize>().ok()?..=end.parse().ok()?),
+            None => list.push(range.parse().ok()?),
+        }
+    }
+    Some(list)
+}
+
+fn pinned_pool(node: usize, mut cpus: Vec<usize>) -> Option<rayon::ThreadPool> {
+    cpus.retain(|&cpu| cpu < libc::CPU_SETSIZE as usize);
+    if cpus.is_empty() {
+        return None;
+    }
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(cpus.len())
+        .thread_name(move |i| format!("blake3-numa{}-{}", node, i))
+        .start_handler(move |_| pin_current_thread(&cpus))
+        .build()
+        .ok()
+}
+
+fn pin_current_thread(cpus: &[usize]) {
+    // Pinning is only an optimization. Ignore errors.
+    unsafe {
+        let mut set: libc::cpu_set_t = core::mem::zeroed();
+        for &cpu in cpus {
+            libc::CPU_SET(cpu, &mut set);
+        }
+        libc::sched_setaffinity(0, core::mem::size_of::<libc::cpu_set_t>(), &set);
+    }
+}
+
+// The node that owns the page containing `ptr`. This is None for pages that haven't been faulted
+// in yet, like the untouched parts of a fresh memory map.
+fn page_node(ptr: *const u8) -> Option<usize> {
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    if page_size <= 0 {
+        return None;
+    }
+    let page = (ptr as usize & !(page_size as usize - 1)) as *mut libc::c_void;
+    let mut status: libc::c_int = -1;
+    // With a null `nodes` argument, move_pages only reports where each page is.
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_move_pages,
+            0 as libc::c_int,
+            1 as libc::c_ulong,
+            &page as *const *mut libc::c_void,
+            core::ptr::null::<libc::c_int>(),
+            &mut status as *mut libc::c_int,
+            0 as libc::c_int,
+        )
+    };
+    if ret == 0 && status >= 0 {
+        Some(status as usize)
+    } else {
+        None
+    }
+}
+
+pub(crate) fn update<'a>(
+    hasher: &'a mut Hasher,
+    mut input: &[u8],
+    topology: &Topology,
+) -> &'a mut Hasher {
+    if topology.num_pools() < 2 {
+        return hasher.update_rayon(input);
+    }
+    // This follows the same steps as Hasher::update_with_join, except that large subtrees are
+    // split across nodes.
+    loop {
+        input = hasher.finish_partial_chunk(input);
+        if input.len() <= CHUNK_LEN {
+            break;
+        }
+        let subtree_len = hasher.next_subtree_len(input.len());
+        let (subtree, rest) = input.split_at(subtree_len);
+        if subtree_len < 2 * topology.min_subtree_len {
+            hasher.update_with_join::<join::RayonJoin>(subtree, join::default_max_depth());
+        } else {
+            let counter = hasher.chunk_state.chunk_counter;
+            let (left, right) = subtree.split_at(subtree_len / 2);
+            let right_counter = counter + (left.len() / CHUNK_LEN) as u64;
+            let key = hasher.key;
+            let flags = hasher.chunk_state.flags;
+            let platform = hasher.chunk_state.platform;
+            let (left_cv, right_cv) = rayon::join(
+                || subtree_cv(left, &key, counter, flags, platform, topology),
+                || subtree_cv(right, &key, right_counter, flags, platform, topology),
+            );
+            // As in update_with_join, push both CVs, so that the root isn't merged yet.
+            hasher.push_cv(&left_cv, counter);
+            hasher.push_cv(&right_cv, right_counter);
+            hasher.chunk_state.chunk_counter += (subtree_len / CHUNK_LEN) as u64;
+        }
+        input = rest;
+    }
+    // What remains is 1 chunk or less.
+    hasher.update(input)
+}
+
+// The non-root chaining value of a complete subtree of at least two chunks.
+fn subtree_cv(
+    input: &[u8],
+    key: &CVWords,
+    chunk_counter: u64,
+    flags: u8,
+    platform: Platform,
+    topology: &Topology,
+) -> CVBytes {
+    let pool = topology.pool_for(input);
+    if pool.is_none() && input.len() >= 2 * topology.min_subtree_len {
+        // The subtree spans more than one node. Split it.
+        let (left, right) = input.split_at(input.len() / 2);
+        let right_counter = chunk_counter + (left.len() / CHUNK_LEN) as u64;
+        let (left_cv, right_cv) = rayon::join(
+            || subtree_cv(left, key, chunk_counter, flags, platform, topology),
+            || subtree_cv(right, key, right_counter, flags, platform, topology),
+        );
+        return parent_node_output(&left_cv, &right_cv, key, flags, platform).chaining_value();
+    }
+    let compress = || {
+        let cv_pair = crate::compress_subtree_to_parent_node::<join::RayonJoin>(
+            input,
+            key,
+            chunk_counter,
+            flags,
+            platform,
+            join::default_max_depth(),
+        );
+        let left_cv = array_ref!(cv_pair, 0, 32);
+        let right_cv = array_ref!(cv_pair, 32, 32);
+        parent_node_output(left_cv, right_cv, key, flags, platform).chaining_value()
+    };
+    match pool {
+        Some(pool) => pool.install(compress),
+        None => compress(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Pretend that memory alternates between two nodes every four chunks, so that subtrees get
+    // split and sent to both pools.
+    fn fake_topology() -> Topology {
+        let pool = || {
+            Some(
+                rayon::ThreadPoolBuilder::new()
+                    .num_threads(2)
+                    .build()
+                    .unwrap(),
+            )
+        };
+        Topology {
+            pools: vec![pool(), pool()],
+            node_of: |ptr| Some(ptr as usize / (4 * CHUNK_LEN) % 2),
+            min_subtree_len: 2 * CHUNK_LEN,
+        }
+    }
+
+    fn reference_hash(input: &[u8]) -> crate::Hash {
+        let mut hasher = reference_impl::Hasher::new();
+        hasher.update(input);
+        let mut bytes = [0; 32];
+        hasher.finalize(&mut bytes);
+        bytes.into()
+    }
+
+    #[test]
+    fn test_read_list() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("list");
+        let path = path.to_str().unwrap();
+        std::fs::write(path, "0-3,8,10-11\n").unwrap();
+        assert_eq!(read_list(path), Some(vec![0, 1, 2, 3, 8, 10, 11]));
+        std::fs::write(path, "\n").unwrap();
+        assert_eq!(read_list(path), Some(vec![]));
+        std::fs::write(path, "x").unwrap();
+        assert_eq!(read_list(path), None);
+    }
+
+    #[test]
+    fn test_update_numa_fake_topology() {
+        let topology = fake_topology();
+        let mut input = vec![0; 68 * CHUNK_LEN + 12];
+        crate::test::paint_test_input(&mut input);
+        // Start from a few different offsets, so that the subtree planning has to handle partial
+        // chunks and uneven subtrees.
+        for &prefix_len in &[0, 1, CHUNK_LEN, 3 * CHUNK_LEN + 7] {
+            for &len in &[
+                0,
+                CHUNK_LEN,
+                8 * CHUNK_LEN,
+                33 * CHUNK_LEN,
+                64 * CHUNK_LEN + 5,
+            ] {
+                let end = prefix_len + len;
+                let mut hasher = Hasher::new();
+                hasher.update(&input[..prefix_len]);
+                update(&mut hasher, &input[prefix_len..end], &topology);
+                assert_eq!(
+                    hasher.finalize(),
+                    reference_hash(&input[..end]),
+                    "prefix {} len {}",
+                    prefix_len,
+                    len,
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_update_rayon_numa_system() {
+        let mut input = vec![0; 100 * CHUNK_LEN + 1];
+        crate::test::paint_test_input(&mut input);
+        let mut hasher = Hasher::new();
+        hasher.update_rayon_numa(&input);
+        assert_eq!(hasher.finalize(), reference_hash(&input));
+    }
+}
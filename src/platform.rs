@@ -1,5 +1,6 @@
-use crate::{portable, CVWords, IncrementCounter, BLOCK_LEN};
+use crate::{portable, CVWords, Hash, IncrementCounter, BLOCK_LEN, CHUNK_LEN, OUT_LEN};
 use arrayref::{array_mut_ref, array_ref};
+use arrayvec::ArrayVec;
 
 cfg_if::cfg_if! {
     if #[cfg(any(target_arch = "x86", target_arch = "x86_64"))] {
@@ -53,9 +54,68 @@ pub enum Platform {
     NEON,
 }
 
+// The result of dynamic feature detection on x86, encoded with Platform::to_cache_value(). Zero
+// means detection hasn't run yet. Races are harmless, since every thread detects the same thing.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+static DETECTED: core::sync::atomic::AtomicU8 = core::sync::atomic::AtomicU8::new(0);
+
 impl Platform {
-    #[allow(unreachable_code)]
+    // This runs for every call to hash() and friends, so keep it small enough to inline. The
+    // first call on x86 takes the cold path, and after that it's a single load.
+    #[inline(always)]
     pub fn detect() -> Self {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            use core::sync::atomic::Ordering::Relaxed;
+            match Self::from_cache_value(DETECTED.load(Relaxed)) {
+                Some(platform) => platform,
+                None => Self::detect_slow(),
+            }
+        }
+        // Elsewhere, detection is all compile-time constants, and there's nothing to cache.
+        #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+        {
+            Self::detect_uncached()
+        }
+    }
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    #[cold]
+    fn detect_slow() -> Self {
+        use core::sync::atomic::Ordering::Relaxed;
+        let platform = Self::detect_uncached();
+        DETECTED.store(platform.to_cache_value(), Relaxed);
+        platform
+    }
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    fn to_cache_value(self) -> u8 {
+        match self {
+            Platform::Portable => 1,
+            Platform::SSE2 => 2,
+            Platform::SSE41 => 3,
+            Platform::AVX2 => 4,
+            #[cfg(blake3_avx512_ffi)]
+            Platform::AVX512 => 5,
+        }
+    }
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    #[inline(always)]
+    fn from_cache_value(value: u8) -> Option<Self> {
+        match value {
+            1 => Some(Platform::Portable),
+            2 => Some(Platform::SSE2),
+            3 => Some(Platform::SSE41),
+            4 => Some(Platform::AVX2),
+            #[cfg(blake3_avx512_ffi)]
+            5 => Some(Platform::AVX512),
+            _ => None,
+        }
+    }
+
+    #[allow(unreachable_code)]
+    fn detect_uncached() -> Self {
         #[cfg(miri)]
         {
             return Platform::Portable;
@@ -181,6 +241,7 @@ impl Platform {
     // after every block, there's a small but measurable performance loss.
     // Compressing chunks with a dedicated loop avoids this.
 
+    #[allow(clippy::too_many_arguments)]
     pub fn hash_many<const N: usize>(
         &self,
         inputs: &[&[u8; N]],
@@ -277,6 +338,91 @@ impl Platform {
         }
     }
 
+    // Hash many independent inputs of varying lengths, writing the root hash of
+    // inputs[i] to out[i]. The key and flags are as in hash_many(), e.g. IV
+    // and 0 for the regular hash function. Inputs that are a whole number of
+    // blocks and at most one chunk are grouped by length and hashed in
+    // parallel with hash_many(), with ROOT set on their final blocks. Every
+    // other input (including the empty input) is hashed serially.
+    pub fn hash_many_variable(&self, inputs: &[&[u8]], key: &CVWords, flags: u8, out: &mut [Hash]) {
+        assert_eq!(inputs.len(), out.len(), "mismatched output length");
+        debug_assert_eq!(CHUNK_LEN, 16 * BLOCK_LEN);
+        self.hash_many_same_len::<64>(inputs, key, flags, out);
+        self.hash_many_same_len::<128>(inputs, key, flags, out);
+        self.hash_many_same_len::<192>(inputs, key, flags, out);
+        self.hash_many_same_len::<256>(inputs, key, flags, out);
+        self.hash_many_same_len::<320>(inputs, key, flags, out);
+        self.hash_many_same_len::<384>(inputs, key, flags, out);
+        self.hash_many_same_len::<448>(inputs, key, flags, out);
+        self.hash_many_same_len::<512>(inputs, key, flags, out);
+        self.hash_many_same_len::<576>(inputs, key, flags, out);
+        self.hash_many_same_len::<640>(inputs, key, flags, out);
+        self.hash_many_same_len::<704>(inputs, key, flags, out);
+        self.hash_many_same_len::<768>(inputs, key, flags, out);
+        self.hash_many_same_len::<832>(inputs, key, flags, out);
+        self.hash_many_same_len::<896>(inputs, key, flags, out);
+        self.hash_many_same_len::<960>(inputs, key, flags, out);
+        self.hash_many_same_len::<1024>(inputs, key, flags, out);
+        for (input, hash) in inputs.iter().zip(out.iter_mut()) {
+            if !is_whole_blocks_in_one_chunk(input.len()) {
+                *hash = crate::hash_all_at_once::<crate::join::SerialJoin>(input, key, flags)
+                    .root_hash();
+            }
+        }
+    }
+
+    fn hash_many_same_len<const N: usize>(
+        &self,
+        inputs: &[&[u8]],
+        key: &CVWords,
+        flags: u8,
+        out: &mut [Hash],
+    ) {
+        let mut batch = ArrayVec::<&[u8; N], MAX_SIMD_DEGREE>::new();
+        let mut batch_indexes = ArrayVec::<usize, MAX_SIMD_DEGREE>::new();
+        for (i, &input) in inputs.iter().enumerate() {
+            let Ok(input) = <&[u8; N]>::try_from(input) else {
+                continue;
+            };
+            batch.push(input);
+            batch_indexes.push(i);
+            if batch.is_full() {
+                self.hash_many_roots(&batch, &batch_indexes, key, flags, out);
+                batch.clear();
+                batch_indexes.clear();
+            }
+        }
+        if !batch.is_empty() {
+            self.hash_many_roots(&batch, &batch_indexes, key, flags, out);
+        }
+    }
+
+    // Each input here is an entire single-chunk message, so they all use
+    // chunk counter 0, and the final block of each one is a root block.
+    fn hash_many_roots<const N: usize>(
+        &self,
+        batch: &[&[u8; N]],
+        batch_indexes: &[usize],
+        key: &CVWords,
+        flags: u8,
+        out: &mut [Hash],
+    ) {
+        let mut roots = [0; MAX_SIMD_DEGREE * OUT_LEN];
+        self.hash_many(
+            batch,
+            key,
+            0,
+            IncrementCounter::No,
+            flags,
+            crate::CHUNK_START,
+            crate::CHUNK_END | crate::ROOT,
+            &mut roots,
+        );
+        for (&i, root) in batch_indexes.iter().zip(roots.chunks_exact(OUT_LEN)) {
+            out[i] = Hash(*array_ref!(root, 0, OUT_LEN));
+        }
+    }
+
     // Explicit platform constructors, for benchmarks.
 
     pub fn portable() -> Self {
@@ -325,6 +471,30 @@ impl Platform {
         // Assumed to be safe if the "neon" feature is on.
         Some(Self::NEON)
     }
+
+    // Platforms returned by detect() and the constructors above are always available, but a
+    // caller can also name a variant directly.
+    pub fn is_available(&self) -> bool {
+        match self {
+            Platform::Portable => true,
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Platform::SSE2 => sse2_detected(),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Platform::SSE41 => sse41_detected(),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Platform::AVX2 => avx2_detected(),
+            #[cfg(blake3_avx512_ffi)]
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Platform::AVX512 => avx512_detected(),
+            // Assumed to be safe if the "neon" feature is on.
+            #[cfg(blake3_neon)]
+            Platform::NEON => true,
+        }
+    }
+}
+
+fn is_whole_blocks_in_one_chunk(len: usize) -> bool {
+    len > 0 && len <= CHUNK_LEN && len & (BLOCK_LEN - 1) == 0
 }
 
 // Note that AVX-512 is divided into multiple featuresets, and we use two of
@@ -439,6 +609,7 @@ pub fn sse2_detected() -> bool {
 }
 
 #[inline(always)]
+#[allow(clippy::erasing_op, clippy::identity_op)]
 pub fn words_from_le_bytes_32(bytes: &[u8; 32]) -> [u32; 8] {
     let mut out = [0; 8];
     out[0] = u32::from_le_bytes(*array_ref!(bytes, 0 * 4, 4));
@@ -453,6 +624,7 @@ pub fn words_from_le_bytes_32(bytes: &[u8; 32]) -> [u32; 8] {
 }
 
 #[inline(always)]
+#[allow(clippy::erasing_op, clippy::identity_op)]
 pub fn words_from_le_bytes_64(bytes: &[u8; 64]) -> [u32; 16] {
     let mut out = [0; 16];
     out[0] = u32::from_le_bytes(*array_ref!(bytes, 0 * 4, 4));
@@ -475,6 +647,7 @@ pub fn words_from_le_bytes_64(bytes: &[u8; 64]) -> [u32; 16] {
 }
 
 #[inline(always)]
+#[allow(clippy::erasing_op, clippy::identity_op)]
 pub fn le_bytes_from_words_32(words: &[u32; 8]) -> [u8; 32] {
     let mut out = [0; 32];
     *array_mut_ref!(out, 0 * 4, 4) = words[0].to_le_bytes();
@@ -489,6 +662,7 @@ pub fn le_bytes_from_words_32(words: &[u32; 8]) -> [u8; 32] {
 }
 
 #[inline(always)]
+#[allow(clippy::erasing_op, clippy::identity_op)]
 pub fn le_bytes_from_words_64(words: &[u32; 16]) -> [u8; 64] {
     let mut out = [0; 64];
     *array_mut_ref!(out, 0 * 4, 4) = words[0].to_le_bytes();
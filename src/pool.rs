@@ -0,0 +1,150 @@
+//! A pool of reusable hashers, for services that hash many independent inputs.
+//!
+//! See [`HasherPool`].
+
+use crate::Hasher;
+use std::ops::{Deref, DerefMut};
+use std::sync::Mutex;
+
+/// A thread-safe pool of [`Hasher`]s.
+///
+/// [`get`](HasherPool::get) returns a [`PooledHasher`] guard, which dereferences to a `Hasher` in
+/// its initial state. When the guard is dropped, the hasher is [`reset`](Hasher::reset) and
+/// returned to the pool. Every hasher handed out by a pool uses the same mode (regular, keyed, or
+/// derive-key) as the hasher the pool was created from.
+///
+/// Note that a pool created from a keyed or derive-key hasher retains that key in memory for as
+/// long as the pool exists.
+///
+/// This type requires the `std` Cargo feature, which is enabled by default.
+///
+/// # Example
+///
+/// ```
+/// let pool = blake3::HasherPool::new();
+/// let hash = pool.get().update(b"foo").finalize();
+/// assert_eq!(hash, blake3::hash(b"foo"));
+/// ```
+#[derive(Debug)]
+pub struct HasherPool {
+    template: Hasher,
+    hashers: Mutex<Vec<Hasher>>,
+}
+
+impl HasherPool {
+    /// Construct an empty pool of regular hashers, as [`Hasher::new`] would return.
+    pub fn new() -> Self {
+        Self::from_hasher(Hasher::new())
+    }
+
+    /// Construct an empty pool of hashers with the same mode as `hasher`. Any input that's
+    /// already been written to `hasher` is discarded.
+    pub fn from_hasher(mut hasher: Hasher) -> Self {
+        hasher.reset();
+        Self {
+            template: hasher,
+            hashers: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Take a hasher from the pool, or create a new one if the pool is empty.
+    pub fn get(&self) -> PooledHasher<'_> {
+        let hasher = self.lock().pop().unwrap_or_else(|| self.template.clone());
+        PooledHasher {
+            pool: self,
+            hasher: Some(hasher),
+        }
+    }
+
+    /// The number of idle hashers currently in the pool.
+    pub fn idle_len(&self) -> usize {
+        self.lock().len()
+    }
+
+    // Pooled hashers are always reset before they're returned, so a panic while the lock is held
+    // can't leave anything inconsistent behind. Ignore poisoning.
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Hasher>> {
+        self.hashers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Default for HasherPool {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A [`Hasher`] borrowed from a [`HasherPool`], which returns to the pool when dropped.
+#[derive(Debug)]
+pub struct PooledHasher<'a> {
+    pool: &'a HasherPool,
+    // Always Some until drop.
+    hasher: Option<Hasher>,
+}
+
+impl Deref for PooledHasher<'_> {
+    type Target = Hasher;
+
+    #[inline]
+    fn deref(&self) -> &Hasher {
+        self.hasher.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledHasher<'_> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Hasher {
+        self.hasher.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledHasher<'_> {
+    fn drop(&mut self) {
+        if let Some(mut hasher) = self.hasher.take() {
+            hasher.reset();
+            self.pool.lock().push(hasher);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pool() {
+        let pool = HasherPool::new();
+        assert_eq!(pool.idle_len(), 0);
+        {
+            let mut hasher1 = pool.get();
+            let mut hasher2 = pool.get();
+            hasher1.update(b"foo");
+            hasher2.update(b"bar");
+            assert_eq!(hasher1.finalize(), Hasher::new().update(b"foo").finalize());
+            assert_eq!(hasher2.finalize(), Hasher::new().update(b"bar").finalize());
+        }
+        assert_eq!(pool.idle_len(), 2);
+
+        // Reused hashers come back reset.
+        let mut hasher = pool.get();
+        assert_eq!(pool.idle_len(), 1);
+        assert_eq!(hasher.count(), 0);
+        hasher.update(b"baz");
+        assert_eq!(hasher.finalize(), crate::hash(b"baz"));
+    }
+
+    #[test]
+    fn test_pool_keyed() {
+        let key = [42; crate::KEY_LEN];
+        let mut template = Hasher::new_keyed(&key);
+        template.update(b"discarded");
+        let pool = HasherPool::from_hasher(template);
+        for _ in 0..3 {
+            let mut hasher = pool.get();
+            hasher.update(b"foo");
+            assert_eq!(hasher.finalize(), crate::keyed_hash(&key, b"foo"));
+        }
+        assert_eq!(pool.idle_len(), 1);
+    }
+}
@@ -150,6 +150,7 @@ pub fn hash1<const N: usize>(
     *out = crate::platform::le_bytes_from_words_32(&cv);
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn hash_many<const N: usize>(
     inputs: &[&[u8; N]],
     key: &CVWords,
@@ -672,6 +672,7 @@ unsafe fn hash1<const N: usize>(
 }
 
 #[target_feature(enable = "sse2")]
+#[allow(clippy::too_many_arguments)]
 pub unsafe fn hash_many<const N: usize>(
     mut inputs: &[&[u8; N]],
     key: &CVWords,
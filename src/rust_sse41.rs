@@ -663,6 +663,7 @@ unsafe fn hash1<const N: usize>(
 }
 
 #[target_feature(enable = "sse4.1")]
+#[allow(clippy::too_many_arguments)]
 pub unsafe fn hash_many<const N: usize>(
     mut inputs: &[&[u8; N]],
     key: &CVWords,
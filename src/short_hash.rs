@@ -0,0 +1,178 @@
+//! Truncated hashes with their length in the type.
+//!
+//! See [`ShortHash`].
+
+use crate::{hex_val, push_hex, HexError, HexErrorInner, OUT_LEN};
+use arrayvec::ArrayString;
+use core::fmt;
+
+/// A BLAKE3 output truncated to `N` bytes, returned by
+/// [`Hasher::finalize_short`](crate::Hasher::finalize_short).
+///
+/// Protocols that standardize on an output length shorter than 32 bytes can use `ShortHash<N>` to
+/// keep outputs of different lengths from being mixed up. Like [`Hash`](crate::Hash), it provides
+/// constant-time equality checking, conversion to and from hexadecimal, and (with the `serde`
+/// Cargo feature) serialization as an array of `N` bytes. `N` must be between 1 and 32 inclusive,
+/// which is checked at compile time.
+///
+/// Note that outputs shorter than 32 bytes provide less security. An N-byte output provides at
+/// most 8N bits of preimage resistance and 4N bits of collision resistance.
+///
+/// # Example
+///
+/// ```
+/// let mut hasher = blake3::Hasher::new();
+/// hasher.update(b"foo");
+/// let short: blake3::ShortHash<20> = hasher.finalize_short();
+/// assert_eq!(short.as_bytes(), &hasher.finalize().as_bytes()[..20]);
+/// assert_eq!(short, short.to_hex().parse().unwrap());
+/// ```
+#[cfg_attr(feature = "zeroize", derive(zeroize::Zeroize))]
+#[derive(Clone, Copy)]
+pub struct ShortHash<const N: usize>([u8; N]);
+
+impl<const N: usize> ShortHash<N> {
+    const VALID_LEN: () = assert!(N > 0 && N <= OUT_LEN, "ShortHash length must be 1..=32");
+
+    /// The raw bytes of the `ShortHash`. Note that byte arrays don't provide constant-time
+    /// equality checking, so if you need to compare hashes, prefer the `ShortHash` type.
+    #[inline]
+    pub const fn as_bytes(&self) -> &[u8; N] {
+        &self.0
+    }
+
+    /// Create a `ShortHash` from its raw bytes representation.
+    pub const fn from_bytes(bytes: [u8; N]) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::VALID_LEN;
+        Self(bytes)
+    }
+
+    /// Encode a `ShortHash` in lowercase hexadecimal. The result is `2 * N` characters long.
+    ///
+    /// The returned [`ArrayString`] is a fixed size and doesn't allocate memory on the heap.
+    ///
+    /// [`ArrayString`]: https://docs.rs/arrayvec/0.5.1/arrayvec/struct.ArrayString.html
+    pub fn to_hex(&self) -> ArrayString<{ 2 * OUT_LEN }> {
+        let mut s = ArrayString::new();
+        push_hex(&mut s, &self.0);
+        s
+    }
+
+    /// Decode a `ShortHash` from hexadecimal. Both uppercase and lowercase ASCII bytes are
+    /// supported.
+    ///
+    /// Any byte outside the ranges `'0'...'9'`, `'a'...'f'`, and `'A'...'F'` results in an
+    /// error. An input length other than `2 * N` also results in an error.
+    pub fn from_hex(hex: impl AsRef<[u8]>) -> Result<Self, HexError> {
+        let hex_bytes: &[u8] = hex.as_ref();
+        if hex_bytes.len() != N * 2 {
+            return Err(HexError(HexErrorInner::InvalidLen {
+                expected: N * 2,
+                received: hex_bytes.len(),
+            }));
+        }
+        let mut hash_bytes = [0; N];
+        for i in 0..N {
+            hash_bytes[i] = 16 * hex_val(hex_bytes[2 * i])? + hex_val(hex_bytes[2 * i + 1])?;
+        }
+        Ok(Self::from_bytes(hash_bytes))
+    }
+}
+
+impl<const N: usize> From<[u8; N]> for ShortHash<N> {
+    #[inline]
+    fn from(bytes: [u8; N]) -> Self {
+        Self::from_bytes(bytes)
+    }
+}
+
+impl<const N: usize> From<ShortHash<N>> for [u8; N] {
+    #[inline]
+    fn from(hash: ShortHash<N>) -> Self {
+        hash.0
+    }
+}
+
+/// This implementation is constant-time.
+impl<const N: usize> PartialEq for ShortHash<N> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        crate::ct::eq_slices(&self.0, &other.0)
+    }
+}
+
+impl<const N: usize> Eq for ShortHash<N> {}
+
+impl<const N: usize> core::hash::Hash for ShortHash<N> {
+    #[inline]
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        state.write(&self.0);
+    }
+}
+
+impl<const N: usize> core::str::FromStr for ShortHash<N> {
+    type Err = HexError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_hex(s)
+    }
+}
+
+impl<const N: usize> fmt::Display for ShortHash<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_hex().as_str())
+    }
+}
+
+impl<const N: usize> fmt::Debug for ShortHash<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let hex = self.to_hex();
+        let hex: &str = hex.as_str();
+        f.debug_tuple("ShortHash").field(&hex).finish()
+    }
+}
+
+// Serde only derives array impls up to 32 elements, and not generically over N. Serialize the
+// same way those impls do, as a tuple of N bytes, so that ShortHash<32> and Hash are compatible.
+#[cfg(feature = "serde")]
+impl<const N: usize> serde::Serialize for ShortHash<N> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeTuple;
+        let mut tuple = serializer.serialize_tuple(N)?;
+        for byte in &self.0 {
+            tuple.serialize_element(byte)?;
+        }
+        tuple.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, const N: usize> serde::Deserialize<'de> for ShortHash<N> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor<const N: usize>;
+
+        impl<'de, const N: usize> serde::de::Visitor<'de> for Visitor<N> {
+            type Value = ShortHash<N>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "an array of {} bytes", N)
+            }
+
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(
+                self,
+                mut seq: A,
+            ) -> Result<Self::Value, A::Error> {
+                let mut bytes = [0; N];
+                for (i, byte) in bytes.iter_mut().enumerate() {
+                    *byte = seq
+                        .next_element()?
+                        .ok_or_else(|| serde::de::Error::invalid_length(i, &self))?;
+                }
+                Ok(ShortHash::from_bytes(bytes))
+            }
+        }
+
+        deserializer.deserialize_tuple(N, Visitor)
+    }
+}
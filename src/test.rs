@@ -1,7 +1,6 @@
 use crate::{CVBytes, CVWords, IncrementCounter, BLOCK_LEN, CHUNK_LEN, OUT_LEN};
 use arrayref::array_ref;
 use arrayvec::ArrayVec;
-use core::usize;
 use rand::prelude::*;
 
 // Interesting input lengths to run tests on.
@@ -83,13 +82,12 @@ pub fn test_compress_fn(compress_in_place_fn: CompressInPlaceFn, compress_xof_fn
     let flags = crate::CHUNK_END | crate::ROOT | crate::KEYED_HASH;
 
     let portable_out =
-        crate::portable::compress_xof(&initial_state, &block, block_len, counter as u64, flags);
+        crate::portable::compress_xof(&initial_state, &block, block_len, counter, flags);
 
     let mut test_state = initial_state;
-    unsafe { compress_in_place_fn(&mut test_state, &block, block_len, counter as u64, flags) };
+    unsafe { compress_in_place_fn(&mut test_state, &block, block_len, counter, flags) };
     let test_state_bytes = crate::platform::le_bytes_from_words_32(&test_state);
-    let test_xof =
-        unsafe { compress_xof_fn(&initial_state, &block, block_len, counter as u64, flags) };
+    let test_xof = unsafe { compress_xof_fn(&initial_state, &block, block_len, counter, flags) };
 
     assert_eq!(&portable_out[..32], &test_state_bytes[..]);
     assert_eq!(&portable_out[..], &test_xof[..]);
@@ -206,6 +204,42 @@ pub fn test_hash_many_fn(
     }
 }
 
+#[test]
+fn test_hash_many_variable() {
+    let mut input_buf = [0; 4 * CHUNK_LEN];
+    paint_test_input(&mut input_buf);
+    // Lots of lengths that hash_many() can batch, some repeated more than
+    // MAX_SIMD_DEGREE times, mixed with lengths that it can't.
+    let mut lengths = ArrayVec::<usize, 100>::new();
+    for i in 0..40 {
+        lengths.push(BLOCK_LEN * (1 + i % 3));
+        lengths.push(i * 37);
+    }
+    lengths.extend([CHUNK_LEN, CHUNK_LEN + 1, 2 * CHUNK_LEN, 3 * CHUNK_LEN]);
+    // Use different offsets, so that equal-length inputs have different bytes.
+    let mut inputs = ArrayVec::<&[u8], 100>::new();
+    for (i, &len) in lengths.iter().enumerate() {
+        inputs.push(&input_buf[i % 7..][..len]);
+    }
+
+    let mut platforms = ArrayVec::<crate::platform::Platform, 2>::new();
+    platforms.push(crate::platform::Platform::portable());
+    platforms.push(crate::platform::Platform::detect());
+    for platform in platforms {
+        let mut out = [crate::Hash::from_bytes([0; 32]); 100];
+        let out = &mut out[..inputs.len()];
+        platform.hash_many_variable(&inputs, crate::IV, 0, out);
+        for (input, hash) in inputs.iter().zip(out.iter()) {
+            assert_eq!(crate::hash(input), *hash, "len {}", input.len());
+        }
+
+        platform.hash_many_variable(&inputs, &TEST_KEY_WORDS, crate::KEYED_HASH, out);
+        for (input, hash) in inputs.iter().zip(out.iter()) {
+            assert_eq!(crate::keyed_hash(&TEST_KEY, input), *hash);
+        }
+    }
+}
+
 #[test]
 fn test_key_bytes_equal_key_words() {
     assert_eq!(
@@ -306,6 +340,13 @@ fn test_compare_reference_impl() {
                 assert_eq!(hasher.finalize(), *array_ref!(expected_out, 0, 32));
                 assert_eq!(hasher.finalize(), test_out);
             }
+            // incremental (rayon, depth-limited)
+            #[cfg(feature = "rayon")]
+            for max_depth in [0, 1, 2] {
+                let mut hasher = crate::Hasher::new();
+                hasher.update_rayon_with_max_depth(input, max_depth);
+                assert_eq!(hasher.finalize(), test_out);
+            }
             // xof
             let mut extended = [0; OUT];
             hasher.finalize_xof().fill(&mut extended);
@@ -417,6 +458,44 @@ fn test_compare_update_multiple() {
     }
 }
 
+// test_compare_update_multiple() stops at 4 * CHUNK_LEN to keep debug-mode
+// tests fast. This covers update() calls that start or end exactly on the
+// larger subtree boundaries that matter for AVX-512 (16 chunks) and for
+// uneven stacks of subtrees (31 = 16 + 8 + 4 + 2 + 1 chunks).
+#[test]
+fn test_compare_update_simd_boundaries() {
+    const BOUNDARIES: &[usize] = &[
+        1,
+        CHUNK_LEN,
+        16 * CHUNK_LEN - 1,
+        16 * CHUNK_LEN,
+        16 * CHUNK_LEN + 1,
+        31 * CHUNK_LEN - 1,
+        31 * CHUNK_LEN,
+        31 * CHUNK_LEN + 1,
+    ];
+    let mut input_buf = [0; 2 * 31 * CHUNK_LEN + 2];
+    paint_test_input(&mut input_buf);
+
+    for &first_update in BOUNDARIES {
+        let mut test_hasher = crate::Hasher::new();
+        test_hasher.update(&input_buf[..first_update]);
+        for &second_update in BOUNDARIES {
+            let second_input = &input_buf[first_update..][..second_update];
+            let total_input = &input_buf[..first_update + second_update];
+            let mut test_hasher = test_hasher.clone();
+            test_hasher.update(second_input);
+            assert_eq!(
+                reference_hash(total_input),
+                test_hasher.finalize(),
+                "first update {}, second update {}",
+                first_update,
+                second_update,
+            );
+        }
+    }
+}
+
 #[test]
 fn test_fuzz_hasher() {
     const INPUT_MAX: usize = 4 * CHUNK_LEN;
@@ -450,6 +529,38 @@ fn test_fuzz_hasher() {
 }
 
 #[test]
+#[cfg(feature = "std")]
+fn test_fuzz_update_chunks_smoke() {
+    let mut input = [0; 3 * CHUNK_LEN + 1];
+    paint_test_input(&mut input);
+    // Split points around block and chunk boundaries, which a fuzzer would
+    // need to find on its own.
+    let cases: &[&[usize]] = &[
+        &[],
+        &[0],
+        &[0, 0, 0],
+        &[1],
+        &[BLOCK_LEN],
+        &[BLOCK_LEN - 1, 2],
+        &[CHUNK_LEN],
+        &[CHUNK_LEN - 1, 1, 0, CHUNK_LEN + 1],
+        &[1, CHUNK_LEN, CHUNK_LEN, CHUNK_LEN],
+        &[2 * CHUNK_LEN + 1, CHUNK_LEN],
+    ];
+    for case in cases {
+        let mut offset = 0;
+        let mut chunks = Vec::new();
+        for &len in *case {
+            chunks.push(input[offset..][..len].to_vec());
+            offset += len;
+        }
+        crate::fuzz_update_chunks(&chunks, reference_hash);
+    }
+}
+
+#[test]
+// SeekFrom::Current(0) is exactly what we want to exercise below.
+#[allow(clippy::seek_from_current)]
 fn test_xof_seek() {
     let mut out = [0; 533];
     let mut hasher = crate::Hasher::new();
@@ -493,6 +604,469 @@ fn test_xof_seek() {
     }
 }
 
+#[test]
+fn test_xof_seek_native() {
+    use crate::{XofSeek, XofSeekFrom};
+
+    let mut hasher = crate::Hasher::new();
+    hasher.update(b"foo");
+    let mut baseline = hasher.finalize_xof();
+    let mut reader = hasher.finalize_xof();
+    let mut expected = [0; 17];
+    let mut found = [0; 17];
+
+    let steps = [
+        (XofSeekFrom::Start(303), 303),
+        (XofSeekFrom::Current(0), 303 + 17),
+        (XofSeekFrom::Current(-5), 303 + 17 + 17 - 5),
+        (XofSeekFrom::Current(1000), 303 + 3 * 17 - 5 + 1000),
+        (XofSeekFrom::Start(0), 0),
+    ];
+    for (pos, target) in steps {
+        assert_eq!(reader.seek_xof(pos).unwrap(), target);
+        assert_eq!(XofSeek::position(&reader), target);
+        baseline.set_position(target);
+        baseline.fill(&mut expected);
+        reader.fill(&mut found);
+        assert_eq!(expected, found);
+    }
+
+    // Seeking before the start is an error, and it doesn't move the reader.
+    assert!(reader.seek_xof(XofSeekFrom::Current(-18)).is_err());
+    assert_eq!(reader.position(), 17);
+    assert!(reader.seek_xof(XofSeekFrom::Current(i64::MIN)).is_err());
+    assert_eq!(reader.position(), 17);
+
+    // Seeking past the maximum position saturates.
+    reader.seek_xof(XofSeekFrom::Start(u64::MAX - 1)).unwrap();
+    assert_eq!(
+        reader.seek_xof(XofSeekFrom::Current(i64::MAX)).unwrap(),
+        u64::MAX
+    );
+
+    // With std::io::Seek in scope too, method calls are still unambiguous.
+    #[cfg(feature = "std")]
+    {
+        use std::io::Seek;
+        assert_eq!(reader.seek(std::io::SeekFrom::Start(5)).unwrap(), 5);
+        assert_eq!(reader.seek_xof(XofSeekFrom::Current(5)).unwrap(), 10);
+    }
+}
+
+#[test]
+fn test_xof_seek_max_position() {
+    let mut hasher = crate::Hasher::new();
+    hasher.update(b"foo");
+    let output = hasher.final_output();
+
+    // Seek to the start of the last block that a u64 position can address.
+    let last_block_start = u64::MAX - (BLOCK_LEN as u64 - 1);
+    let last_counter = u64::MAX / BLOCK_LEN as u64;
+    let mut reader = hasher.finalize_xof();
+    reader.set_position(last_block_start);
+    assert_eq!(reader.position(), last_block_start);
+    assert_eq!(reader.inner.counter, last_counter);
+
+    // Fill that whole block, and make sure we got the right one.
+    let mut block = [0; BLOCK_LEN];
+    reader.fill(&mut block);
+    let expected = crate::portable::compress_xof(
+        &output.input_chaining_value,
+        &output.block,
+        output.block_len,
+        last_counter,
+        output.flags | crate::ROOT,
+    );
+    assert_eq!(block, expected);
+
+    // The position one past the last byte isn't representable. It saturates
+    // rather than wrapping around to 0.
+    assert_eq!(reader.position(), u64::MAX);
+
+    // Seeking to the maximum position and reading one byte gives the last
+    // byte of that block.
+    reader.set_position(u64::MAX);
+    assert_eq!(reader.position(), u64::MAX);
+    let mut last_byte = [0];
+    reader.fill(&mut last_byte);
+    assert_eq!(last_byte[0], expected[BLOCK_LEN - 1]);
+    assert_eq!(reader.position(), u64::MAX);
+
+    #[cfg(feature = "std")]
+    {
+        use std::io::prelude::*;
+        let mut reader = hasher.finalize_xof();
+        assert_eq!(
+            reader.seek(std::io::SeekFrom::Start(u64::MAX)).unwrap(),
+            u64::MAX
+        );
+        assert_eq!(
+            reader.seek(std::io::SeekFrom::Current(i64::MAX)).unwrap(),
+            u64::MAX
+        );
+    }
+}
+
+#[test]
+fn test_fill_empty_and_past_max() {
+    let mut hasher = crate::Hasher::new();
+    hasher.update(b"foo");
+
+    // Filling an empty buffer doesn't move the position or touch the block
+    // cache, at the start, in the middle of a block, and at the very end.
+    for &position in &[0, 10, BLOCK_LEN as u64, u64::MAX - 1, u64::MAX] {
+        let mut reader = hasher.finalize_xof();
+        reader.set_position(position);
+        let cache = reader.block_cache;
+        reader.fill(&mut []);
+        assert_eq!(reader.position(), position);
+        assert_eq!(reader.block_cache, cache);
+        #[cfg(feature = "std")]
+        {
+            use std::io::prelude::*;
+            assert_eq!(reader.read(&mut []).unwrap(), 0);
+            assert_eq!(reader.position(), position);
+        }
+    }
+
+    // Filling across the end of the maximum output gives the last bytes of
+    // the last addressable block first, never panics, and leaves the
+    // position saturated.
+    let mut last_bytes = [0; 11];
+    let mut reader = hasher.finalize_xof();
+    reader.set_position(u64::MAX - 10);
+    reader.fill(&mut last_bytes);
+    assert_eq!(reader.position(), u64::MAX);
+    let mut buf = [0; 3 * BLOCK_LEN];
+    let mut reader = hasher.finalize_xof();
+    reader.set_position(u64::MAX - 10);
+    reader.fill(&mut buf);
+    assert_eq!(buf[..11], last_bytes);
+    assert_eq!(reader.position(), u64::MAX);
+    reader.fill(&mut buf);
+    assert_eq!(reader.position(), u64::MAX);
+
+    // The multithreaded fill agrees with the serial one past the end too.
+    #[cfg(feature = "rayon")]
+    {
+        let mut parallel = [0; 3 * BLOCK_LEN];
+        let mut reader = hasher.finalize_xof();
+        reader.set_position(u64::MAX - 10);
+        reader.fill_rayon(&mut parallel);
+        let mut serial = [0; 3 * BLOCK_LEN];
+        let mut reader = hasher.finalize_xof();
+        reader.set_position(u64::MAX - 10);
+        reader.fill(&mut serial);
+        assert_eq!(serial, parallel);
+        assert_eq!(reader.position(), u64::MAX);
+    }
+}
+
+#[test]
+fn test_finalize_xof_at() {
+    let mut hasher = crate::Hasher::new_keyed(&TEST_KEY);
+    hasher.update(b"nonce");
+
+    let mut expected = [0; 5 * BLOCK_LEN];
+    hasher.finalize_xof().fill(&mut expected);
+
+    // Block 0 for an authenticator key, and blocks 1.. for a keystream.
+    let mut auth_key = [0; 32];
+    hasher.finalize_xof_at(0).fill(&mut auth_key);
+    assert_eq!(auth_key, expected[..32]);
+    let mut keystream_reader = hasher.finalize_xof_at(1);
+    assert_eq!(keystream_reader.position(), BLOCK_LEN as u64);
+    let mut keystream = [0; 4 * BLOCK_LEN];
+    keystream_reader.fill(&mut keystream);
+    assert_eq!(keystream, expected[BLOCK_LEN..]);
+
+    // The same as seeking a regular reader.
+    let mut seek_reader = hasher.finalize_xof();
+    seek_reader.set_position(3 * BLOCK_LEN as u64);
+    let mut seek_out = [0; 100];
+    seek_reader.fill(&mut seek_out);
+    let mut at_out = [0; 100];
+    hasher.finalize_xof_at(3).fill(&mut at_out);
+    assert_eq!(seek_out, at_out);
+
+    // The last block that a position can address is still equivalent to seeking. Past that, the
+    // position saturates.
+    let last_counter = u64::MAX / BLOCK_LEN as u64;
+    let mut seek_reader = hasher.finalize_xof();
+    seek_reader.set_position(last_counter * BLOCK_LEN as u64);
+    let mut at_reader = hasher.finalize_xof_at(last_counter);
+    assert_eq!(at_reader.position(), seek_reader.position());
+    seek_reader.fill(&mut seek_out[..BLOCK_LEN]);
+    at_reader.fill(&mut at_out[..BLOCK_LEN]);
+    assert_eq!(seek_out, at_out);
+    assert_eq!(
+        hasher.finalize_xof_at(last_counter + 1).position(),
+        u64::MAX
+    );
+    assert_eq!(hasher.finalize_xof_at(u64::MAX).position(), u64::MAX);
+}
+
+#[test]
+fn test_output_bytes() {
+    let mut input = [0; 3 * CHUNK_LEN + 1];
+    paint_test_input(&mut input);
+    for hasher in [
+        crate::Hasher::new(),
+        crate::Hasher::new_keyed(&TEST_KEY),
+        crate::Hasher::new_derive_key("test context"),
+    ] {
+        // Cover a root chunk, a partial root chunk, and a root parent node.
+        for &len in &[0, 1, CHUNK_LEN, input.len()] {
+            let mut hasher = hasher.clone();
+            hasher.update(&input[..len]);
+            let mut expected = [0; 5 * BLOCK_LEN];
+            hasher.finalize_xof().fill(&mut expected);
+
+            // Serialize from a reader that's already been read partway, which doesn't matter.
+            let mut reader = hasher.finalize_xof();
+            reader.fill(&mut [0; 100]);
+            let bytes = reader.to_output_bytes();
+            assert_eq!(bytes, hasher.finalize_xof().to_output_bytes());
+
+            for &position in &[0, 1, 63, 64, 65, 3 * BLOCK_LEN as u64 + 1] {
+                let mut reader = crate::OutputReader::from_output_bytes(&bytes, position).unwrap();
+                assert_eq!(reader.position(), position);
+                let mut output = [0; 100];
+                reader.fill(&mut output);
+                let start = position as usize;
+                assert_eq!(output, expected[start..][..100], "len {}", len);
+            }
+        }
+    }
+
+    let bytes = crate::Hasher::new().finalize_xof().to_output_bytes();
+    assert!(crate::OutputReader::from_output_bytes(&bytes[1..], 0).is_err());
+    assert!(crate::OutputReader::from_output_bytes(&[bytes.as_ref(), &[0]].concat(), 0).is_err());
+    let mut bad_block_len = bytes;
+    bad_block_len[32 + BLOCK_LEN] = BLOCK_LEN as u8 + 1;
+    assert!(crate::OutputReader::from_output_bytes(&bad_block_len, 0).is_err());
+    let mut bad_flags = bytes;
+    bad_flags[32 + BLOCK_LEN + 1] |= crate::ROOT;
+    assert!(crate::OutputReader::from_output_bytes(&bad_flags, 0).is_err());
+}
+
+#[test]
+fn test_new_aead() {
+    let mut nonce = [0; CHUNK_LEN + 1];
+    paint_test_input(&mut nonce);
+    for &nonce_len in &[0, 24, nonce.len()] {
+        let nonce = &nonce[..nonce_len];
+        let (mut keystream, auth_key) = crate::Hasher::new_aead(&TEST_KEY, nonce);
+        assert_eq!(keystream.position(), BLOCK_LEN as u64);
+        let mut stream = [0; 3 * BLOCK_LEN + 1];
+        keystream.fill(&mut stream);
+
+        // The same thing by hand, with the keyed XOF.
+        let mut hasher = crate::Hasher::new_keyed(&TEST_KEY);
+        hasher.update(nonce);
+        let mut reader = hasher.finalize_xof();
+        let mut expected_auth_key = [0; 32];
+        reader.fill(&mut expected_auth_key);
+        assert_eq!(auth_key, expected_auth_key);
+        assert_eq!(auth_key, *crate::keyed_hash(&TEST_KEY, nonce).as_bytes());
+        reader.set_position(BLOCK_LEN as u64);
+        let mut expected_stream = [0; 3 * BLOCK_LEN + 1];
+        reader.fill(&mut expected_stream);
+        assert_eq!(stream, expected_stream);
+        // The keystream is the same as starting from output block 1.
+        let mut from_block_1 = [0; 3 * BLOCK_LEN + 1];
+        hasher.finalize_xof_at(1).fill(&mut from_block_1);
+        assert_eq!(stream, from_block_1);
+    }
+}
+
+#[test]
+fn test_finalize_with_length_suffix() {
+    let mut input = [0; 3 * CHUNK_LEN + 1 + 8];
+    paint_test_input(&mut input);
+    for &case in TEST_CASES {
+        if case + 8 > input.len() {
+            continue;
+        }
+        let mut hasher = crate::Hasher::new();
+        hasher.update(&input[..case]);
+        let hash = hasher.finalize_with_length_suffix();
+
+        let mut suffixed = input;
+        suffixed[case..][..8].copy_from_slice(&(case as u64).to_le_bytes());
+        assert_eq!(hash, crate::hash(&suffixed[..case + 8]), "case {}", case);
+
+        // The hasher itself is unchanged.
+        assert_eq!(hasher.count(), case as u64);
+        assert_eq!(hasher.finalize(), crate::hash(&input[..case]));
+    }
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_expand() {
+    let mut input = [0; 3 * CHUNK_LEN + 1];
+    paint_test_input(&mut input);
+    let mut hasher = crate::Hasher::new_keyed(&TEST_KEY);
+    hasher.update(&input);
+
+    let mut expected = [0; 100];
+    hasher.finalize_xof().fill(&mut expected);
+    assert_eq!(&hasher.expand(100)[..], &expected[..]);
+    assert_eq!(&hasher.expand(33)[..], &expected[..33]);
+    assert!(hasher.expand(0).is_empty());
+}
+
+#[test]
+fn test_finalize_with_tag() {
+    let mut input = [0; 3 * CHUNK_LEN + 1];
+    paint_test_input(&mut input);
+    for &case in TEST_CASES {
+        if case > input.len() {
+            continue;
+        }
+        let mut hasher = crate::Hasher::new_keyed(&TEST_KEY);
+        hasher.update(&input[..case]);
+        let (hash, tag) = hasher.finalize_with_tag();
+        assert_eq!(hash, hasher.finalize());
+        let mut reader = hasher.finalize_xof();
+        reader.set_position(32);
+        let mut expected_tag = [0; 16];
+        reader.fill(&mut expected_tag);
+        assert_eq!(tag, expected_tag);
+    }
+}
+
+#[test]
+fn test_finalize_and_verify() {
+    let mut input = [0; 2 * CHUNK_LEN + 1];
+    paint_test_input(&mut input);
+    let expected = crate::hash(&input);
+
+    let mut hasher = crate::Hasher::new();
+    hasher.update(&input);
+    assert_eq!(hasher.finalize_and_verify(&expected), Ok(expected));
+
+    // Flip one byte of the input.
+    input[CHUNK_LEN] ^= 1;
+    let mut hasher = crate::Hasher::new();
+    hasher.update(&input);
+    let err = hasher.finalize_and_verify(&expected).unwrap_err();
+    assert_eq!(err.expected(), &expected);
+    assert_eq!(err.computed(), &crate::hash(&input));
+    #[cfg(feature = "std")]
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "hash mismatch: expected {}, computed {}",
+            expected,
+            crate::hash(&input)
+        )
+    );
+}
+
+#[test]
+fn test_finalize_with_xof() {
+    let mut input = [0; 3 * CHUNK_LEN + 1];
+    paint_test_input(&mut input);
+    let mut hasher = crate::Hasher::new();
+    hasher.update(&input);
+
+    let mut expected = [0; 533];
+    hasher.finalize_xof().fill(&mut expected);
+    assert_eq!(hasher.finalize().as_bytes(), &expected[..32]);
+
+    let (hash, mut reader) = hasher.finalize_with_xof();
+    assert_eq!(hash, hasher.finalize());
+    assert!(reader.block_cache.is_some());
+    let mut out = [0; 533];
+    reader.fill(&mut out);
+    assert_eq!(out, expected);
+
+    // Short reads that stay within a block reuse the cached block, and
+    // seeking to another block invalidates it. Either way, the bytes are the
+    // same as one big read.
+    let (_, mut reader) = hasher.finalize_with_xof();
+    let mut out = [0; 533];
+    for i in 0..out.len() {
+        reader.fill(&mut out[i..i + 1]);
+        assert_eq!(reader.position(), i as u64 + 1);
+    }
+    assert_eq!(out, expected);
+    reader.set_position(303);
+    let mut out2 = [0; 102];
+    reader.fill(&mut out2);
+    assert_eq!(&expected[303..][..102], &out2[..]);
+    reader.set_position(300);
+    reader.fill(&mut out2[..3]);
+    assert_eq!(&expected[300..][..3], &out2[..3]);
+    reader.set_position(0);
+    reader.fill(&mut out2[..3]);
+    assert_eq!(&expected[..3], &out2[..3]);
+}
+
+#[test]
+fn test_into_finalized() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<crate::FinalizedHasher>();
+
+    let mut input = [0; 2 * CHUNK_LEN + 3];
+    paint_test_input(&mut input);
+    let mut hasher = crate::Hasher::new_keyed(&TEST_KEY);
+    hasher.update(&input);
+    let expected_hash = hasher.finalize();
+    let mut expected_xof = [0; 3 * BLOCK_LEN + 5];
+    hasher.finalize_xof().fill(&mut expected_xof);
+
+    let finalized = hasher.into_finalized();
+    for _ in 0..2 {
+        assert_eq!(finalized.hash(), expected_hash);
+        let mut xof = [0; 3 * BLOCK_LEN + 5];
+        finalized.xof().fill(&mut xof);
+        assert_eq!(xof, expected_xof);
+    }
+
+    // Readers are independent, and seeking past the cached first block works.
+    let mut reader = finalized.xof();
+    reader.set_position(BLOCK_LEN as u64 + 1);
+    let mut partial = [0; 10];
+    reader.fill(&mut partial);
+    assert_eq!(partial, expected_xof[BLOCK_LEN + 1..][..10]);
+    reader.set_position(2);
+    reader.fill(&mut partial);
+    assert_eq!(partial, expected_xof[2..][..10]);
+}
+
+#[test]
+fn test_finalize_short() {
+    let mut hasher = crate::Hasher::new();
+    hasher.update(b"foo");
+    let mut expected = [0; 20];
+    hasher.finalize_xof().fill(&mut expected);
+    let short = hasher.finalize_short::<20>();
+    assert_eq!(short.as_bytes(), &expected);
+    assert_eq!(hasher.finalize_array::<20>(), expected);
+    assert_eq!(short, crate::ShortHash::from(expected));
+
+    let hex = short.to_hex();
+    assert_eq!(hex.len(), 40);
+    assert_eq!(&hex[..], &hasher.finalize().to_hex()[..40]);
+    assert_eq!(
+        crate::ShortHash::<20>::from_hex(hex.as_str()).unwrap(),
+        short
+    );
+    assert_eq!(hex.parse::<crate::ShortHash<20>>().unwrap(), short);
+    assert!(crate::ShortHash::<20>::from_hex(&hex[..38]).is_err());
+    assert!(crate::ShortHash::<19>::from_hex(hex.as_str()).is_err());
+
+    let mut other = expected;
+    other[19] ^= 1;
+    assert_ne!(short, crate::ShortHash::from(other));
+
+    let full = hasher.finalize_short::<32>();
+    assert_eq!(full.as_bytes(), hasher.finalize().as_bytes());
+}
+
 #[test]
 fn test_msg_schedule_permutation() {
     let permutation = [2, 6, 3, 10, 7, 0, 4, 13, 1, 11, 12, 5, 9, 14, 15, 8];
@@ -537,79 +1111,756 @@ fn test_reset() {
 }
 
 #[test]
-fn test_hex_encoding_decoding() {
-    let digest_str = "04e0bb39f30b1a3feb89f536c93be15055482df748674b00d26e5a75777702e9";
-    let mut hasher = crate::Hasher::new();
-    hasher.update(b"foo");
-    let digest = hasher.finalize();
-    assert_eq!(digest.to_hex().as_str(), digest_str);
-    #[cfg(feature = "std")]
-    assert_eq!(digest.to_string(), digest_str);
-
-    // Test round trip
-    let digest = crate::Hash::from_hex(digest_str).unwrap();
-    assert_eq!(digest.to_hex().as_str(), digest_str);
-
-    // Test uppercase
-    let digest = crate::Hash::from_hex(digest_str.to_uppercase()).unwrap();
-    assert_eq!(digest.to_hex().as_str(), digest_str);
+fn test_try_update_limit() {
+    let mut input = [0; 2 * CHUNK_LEN];
+    paint_test_input(&mut input);
+    let limit = CHUNK_LEN as u64 + 10;
 
-    // Test string parsing via FromStr
-    let digest: crate::Hash = digest_str.parse().unwrap();
-    assert_eq!(digest.to_hex().as_str(), digest_str);
+    let mut hasher = crate::Hasher::new();
+    hasher.set_max_input(Some(limit));
+    hasher.try_update(&input[..CHUNK_LEN]).unwrap();
+    hasher.try_update(&input[CHUNK_LEN..][..5]).unwrap();
+    // One byte too many fails, and doesn't change the state.
+    let err = hasher.try_update(&input[CHUNK_LEN + 5..][..6]).unwrap_err();
+    assert_eq!(err.max_input(), limit);
+    assert_eq!(hasher.count(), CHUNK_LEN as u64 + 5);
+    // Exactly up to the limit succeeds.
+    hasher.try_update(&input[CHUNK_LEN + 5..][..5]).unwrap();
+    assert_eq!(hasher.count(), limit);
+    assert!(hasher.try_update(&input[..1]).is_err());
+    // Empty input is always fine.
+    hasher.try_update(&[]).unwrap();
+    assert_eq!(hasher.finalize(), crate::hash(&input[..limit as usize]));
+
+    // The limit survives reset, and can be removed.
+    hasher.reset();
+    assert!(hasher.try_update(&input).is_err());
+    hasher.set_max_input(None);
+    hasher.try_update(&input).unwrap();
+    assert_eq!(hasher.finalize(), crate::hash(&input));
+}
 
-    // Test errors
-    let bad_len = "04e0bb39f30b1";
-    let _result = crate::Hash::from_hex(bad_len).unwrap_err();
-    #[cfg(feature = "std")]
-    assert_eq!(_result.to_string(), "expected 64 hex bytes, received 13");
+#[test]
+fn test_mode() {
+    use crate::Mode;
+    let mut hasher = crate::Hasher::new();
+    assert_eq!(hasher.mode(), Mode::Hash);
+    hasher.update(&[42; 3 * CHUNK_LEN + 7]);
+    hasher.reset();
+    assert_eq!(hasher.mode(), Mode::Hash);
 
-    let bad_char = "Z4e0bb39f30b1a3feb89f536c93be15055482df748674b00d26e5a75777702e9";
-    let _result = crate::Hash::from_hex(bad_char).unwrap_err();
-    #[cfg(feature = "std")]
-    assert_eq!(_result.to_string(), "invalid hex character: 'Z'");
+    let mut keyed_hasher = crate::Hasher::new_keyed(&[99; crate::KEY_LEN]);
+    assert_eq!(keyed_hasher.mode(), Mode::Keyed);
+    keyed_hasher.update(&[42; 3 * CHUNK_LEN + 7]);
+    keyed_hasher.reset();
+    assert_eq!(keyed_hasher.mode(), Mode::Keyed);
 
-    let _result = crate::Hash::from_hex([128; 64]).unwrap_err();
-    #[cfg(feature = "std")]
-    assert_eq!(_result.to_string(), "invalid hex character: 0x80");
+    let context = "BLAKE3 2020-02-12 10:20:58 reset test";
+    let mut kdf = crate::Hasher::new_derive_key(context);
+    assert_eq!(kdf.mode(), Mode::DeriveKey);
+    kdf.update(&[42; 3 * CHUNK_LEN + 7]);
+    kdf.reset();
+    assert_eq!(kdf.mode(), Mode::DeriveKey);
+    assert_eq!(
+        crate::Hasher::new_derive_key_salted(context, b"salt").mode(),
+        Mode::DeriveKey,
+    );
 }
 
-// This test is a mimized failure case for the Windows SSE2 bug described in
-// https://github.com/BLAKE3-team/BLAKE3/issues/206.
-//
-// Before that issue was fixed, this test would fail on Windows in the following configuration:
-//
-//     cargo test --features=no_avx512,no_avx2,no_sse41 --release
-//
-// Bugs like this one (stomping on a caller's register) are very sensitive to the details of
-// surrounding code, so it's not especially likely that this test will catch another bug (or even
-// the same bug) in the future. Still, there's no harm in keeping it.
 #[test]
-fn test_issue_206_windows_sse2() {
-    // This stupid loop has to be here to trigger the bug. I don't know why.
-    for _ in &[0] {
-        // The length 65 (two blocks) is significant. It doesn't repro with 64 (one block). It also
-        // doesn't repro with an all-zero input.
-        let input = &[0xff; 65];
-        let expected_hash = [
-            183, 235, 50, 217, 156, 24, 190, 219, 2, 216, 176, 255, 224, 53, 28, 95, 57, 148, 179,
-            245, 162, 90, 37, 121, 0, 142, 219, 62, 234, 204, 225, 161,
-        ];
+fn test_new_derive_key_salted() {
+    let context = "BLAKE3 2024-01-01 00:00:00 salted derive_key test";
+    let key_material = b"some key material";
+    let derive = |salt: &[u8]| {
+        let mut hasher = crate::Hasher::new_derive_key_salted(context, salt);
+        hasher.update(key_material);
+        hasher.finalize()
+    };
 
-        // This throwaway call has to be here to trigger the bug.
-        crate::Hasher::new().update(input);
+    // An empty salt is the same as no salt.
+    assert_eq!(derive(b""), crate::derive_key(context, key_material));
 
-        // This assert fails when the bug is triggered.
-        assert_eq!(crate::Hasher::new().update(input).finalize(), expected_hash);
-    }
+    // Different salts give different keys.
+    assert_ne!(derive(b"salt1"), derive(b"salt2"));
+    assert_ne!(derive(b"salt1"), derive(b""));
+
+    // The documented layout.
+    let mut expected = crate::Hasher::new_derive_key(context);
+    expected.update(&5u64.to_le_bytes());
+    expected.update(b"salt1");
+    expected.update(key_material);
+    assert_eq!(derive(b"salt1"), expected.finalize());
 }
 
 #[test]
-fn test_hash_conversions() {
-    let bytes1 = [42; 32];
-    let hash1: crate::Hash = bytes1.into();
-    let bytes2: [u8; 32] = hash1.into();
-    assert_eq!(bytes1, bytes2);
+fn test_derive_key_context() {
+    let context = "BLAKE3 2019-12-27 16:13:59 example context (not the test vector one)";
+    let derive_key_context = crate::DeriveKeyContext::new(context);
+    let mut input_buf = [0; TEST_CASES_MAX];
+    paint_test_input(&mut input_buf);
+    for &case in TEST_CASES {
+        let input = &input_buf[..case];
+        let expected = crate::derive_key(context, input);
+        assert_eq!(derive_key_context.derive(input), expected, "case {}", case);
+        let mut hasher = derive_key_context.new_hasher();
+        hasher.update(input);
+        assert_eq!(hasher.finalize(), expected, "case {}", case);
+        let mut hasher = crate::Hasher::new_derive_key(context);
+        hasher.update(input);
+        assert_eq!(hasher.finalize(), expected, "case {}", case);
+    }
+    // A clone derives the same keys, and a different context doesn't.
+    assert_eq!(
+        derive_key_context.clone().derive(b"foo"),
+        crate::derive_key(context, b"foo")
+    );
+    assert_ne!(
+        crate::DeriveKeyContext::new("other context").derive(b"foo"),
+        crate::derive_key(context, b"foo")
+    );
+}
+
+#[test]
+fn test_update_framed() {
+    let mut input = [0; 3 * CHUNK_LEN];
+    paint_test_input(&mut input);
+    let (a, b) = input.split_at(CHUNK_LEN + 1);
+
+    let mut expected = crate::Hasher::new();
+    expected.update(&(a.len() as u64).to_le_bytes());
+    expected.update(a);
+    expected.update(&(b.len() as u64).to_le_bytes());
+    expected.update(b);
+    let mut framed = crate::Hasher::new();
+    framed.update_framed(a).update_framed(b);
+    assert_eq!(framed.finalize(), expected.finalize());
+
+    // Unlike plain updates, framed updates aren't just concatenated.
+    let mut concatenated = crate::Hasher::new();
+    concatenated.update_framed(&input);
+    assert_ne!(framed.finalize(), concatenated.finalize());
+    let mut split_differently = crate::Hasher::new();
+    split_differently.update_framed(&input[..1]);
+    split_differently.update_framed(&input[1..]);
+    assert_ne!(framed.finalize(), split_differently.finalize());
+
+    // The empty message still gets a length prefix.
+    let mut empty = crate::Hasher::new();
+    empty.update_framed(b"");
+    assert_eq!(empty.finalize(), crate::hash(&[0; 8]));
+}
+
+#[test]
+fn test_hasher_fmt() {
+    use core::fmt::Write;
+
+    let mut hasher = crate::Hasher::new();
+    write!(hasher.fmt(), "{}-{}", 1, 2).unwrap();
+    assert_eq!(hasher.finalize(), crate::hash(b"1-2"));
+
+    // Writes accumulate, and mix with regular updates.
+    let mut hasher = crate::Hasher::new_keyed(&TEST_KEY);
+    hasher.update(b"a");
+    write!(hasher.fmt(), "{:04x}", 0xbeef).unwrap();
+    let c = 'z';
+    write!(hasher.fmt(), "{c}").unwrap();
+    assert_eq!(hasher.finalize(), crate::keyed_hash(&TEST_KEY, b"abeefz"));
+}
+
+#[test]
+fn test_platform_detect_is_stable() {
+    use crate::platform::Platform;
+    // The first call might do detection, and later calls read the cached result.
+    let first = Platform::detect();
+    assert!(first.is_available());
+    for _ in 0..3 {
+        let platform = Platform::detect();
+        assert_eq!(
+            core::mem::discriminant(&platform),
+            core::mem::discriminant(&first),
+        );
+    }
+}
+
+#[test]
+fn test_hash_with_platform() {
+    use crate::platform::Platform;
+    let mut platforms = ArrayVec::<Platform, 8>::new();
+    platforms.push(Platform::portable());
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        platforms.extend(Platform::sse2());
+        platforms.extend(Platform::sse41());
+        platforms.extend(Platform::avx2());
+    }
+    #[cfg(blake3_avx512_ffi)]
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    platforms.extend(Platform::avx512());
+    #[cfg(blake3_neon)]
+    platforms.extend(Platform::neon());
+
+    let mut input_buf = [0; TEST_CASES_MAX];
+    paint_test_input(&mut input_buf);
+    for &case in TEST_CASES {
+        let input = &input_buf[..case];
+        let expected = crate::hash(input);
+        for &platform in &platforms {
+            assert!(platform.is_available());
+            assert_eq!(
+                crate::hash_with_platform(input, platform),
+                expected,
+                "case {} platform {:?}",
+                case,
+                platform,
+            );
+        }
+    }
+}
+
+#[test]
+fn test_hash_with() {
+    use crate::backend;
+    let mut input_buf = [0; TEST_CASES_MAX];
+    paint_test_input(&mut input_buf);
+    for &case in TEST_CASES {
+        let input = &input_buf[..case];
+        let expected = crate::hash(input);
+        assert_eq!(crate::hash_with::<backend::Portable>(input), expected);
+        #[cfg(all(
+            any(target_arch = "x86", target_arch = "x86_64"),
+            target_feature = "sse2"
+        ))]
+        assert_eq!(crate::hash_with::<backend::SSE2>(input), expected);
+        #[cfg(all(
+            any(target_arch = "x86", target_arch = "x86_64"),
+            target_feature = "avx2"
+        ))]
+        assert_eq!(crate::hash_with::<backend::AVX2>(input), expected);
+        #[cfg(blake3_neon)]
+        assert_eq!(crate::hash_with::<backend::NEON>(input), expected);
+    }
+}
+
+#[test]
+fn test_debug_cv_stack() {
+    use crate::guts::{parent_cv, ChunkState};
+    let mut input = [0; 4 * CHUNK_LEN];
+    paint_test_input(&mut input);
+    let chunk_cv = |i: usize| {
+        ChunkState::new(i as u64)
+            .update(&input[i * CHUNK_LEN..][..CHUNK_LEN])
+            .finalize(false)
+    };
+
+    let mut hasher = crate::Hasher::new();
+    assert_eq!(hasher.cv_stack_len(), 0);
+
+    // The first two chunks get merged into one subtree, and the third chunk
+    // stays in the chunk state, since it could be the root.
+    hasher.update(&input[..3 * CHUNK_LEN]);
+    assert_eq!(hasher.cv_stack_len(), 1);
+    let left_subtree = parent_cv(&chunk_cv(0), &chunk_cv(1), false);
+    assert_eq!(hasher.debug_cv_stack(), &[*left_subtree.as_bytes()]);
+
+    // One more byte finalizes the third chunk, but it isn't merged yet.
+    hasher.update(&input[3 * CHUNK_LEN..][..1]);
+    assert_eq!(hasher.cv_stack_len(), 2);
+    assert_eq!(
+        hasher.debug_cv_stack(),
+        &[*left_subtree.as_bytes(), *chunk_cv(2).as_bytes()],
+    );
+}
+
+#[test]
+fn test_local_hasher() {
+    let mut input = [0; 3 * CHUNK_LEN + 1];
+    paint_test_input(&mut input);
+
+    let mut hasher = crate::LocalHasher::new();
+    hasher.update(&input[..100]).update(&input[100..]);
+    assert_eq!(hasher.finalize(), crate::hash(&input));
+    let mut extended = [0; 100];
+    hasher.finalize_xof().fill(&mut extended);
+    assert_eq!(extended[..32], *crate::hash(&input).as_bytes());
+
+    // Wrapping a keyed hasher keeps its key and its input so far.
+    let mut keyed = crate::Hasher::new_keyed(&TEST_KEY);
+    keyed.update(&input[..100]);
+    let mut hasher = crate::LocalHasher::from(keyed);
+    hasher.update(&input[100..]);
+    assert_eq!(hasher.finalize(), crate::keyed_hash(&TEST_KEY, &input));
+    hasher.reset();
+    assert_eq!(hasher.finalize(), crate::keyed_hash(&TEST_KEY, b""));
+    assert_eq!(hasher.into_inner().count(), 0);
+
+    assert_eq!(
+        core::mem::size_of::<crate::LocalHasher>(),
+        core::mem::size_of::<crate::Hasher>(),
+    );
+}
+
+#[test]
+#[cfg(feature = "rayon")]
+fn test_fill_rayon() {
+    let mut hasher = crate::Hasher::new_keyed(&TEST_KEY);
+    hasher.update(b"foo");
+    // Long enough to split into several spans, and not a multiple of the block size.
+    let len = 100 * 1024 + 7;
+    for &start in &[0, 1, 63, 64, 65, 3 * BLOCK_LEN as u64 + 17] {
+        let mut serial = vec![0; len];
+        let mut serial_reader = hasher.finalize_xof();
+        serial_reader.set_position(start);
+        serial_reader.fill(&mut serial);
+
+        let mut parallel = vec![0; len];
+        let mut parallel_reader = hasher.finalize_xof();
+        parallel_reader.set_position(start);
+        parallel_reader.fill_rayon(&mut parallel);
+        assert_eq!(serial, parallel, "start {}", start);
+        assert_eq!(serial_reader.position(), parallel_reader.position());
+
+        // Both readers continue from the same place.
+        let mut next_serial = [0; 100];
+        let mut next_parallel = [0; 100];
+        serial_reader.fill(&mut next_serial);
+        parallel_reader.fill(&mut next_parallel);
+        assert_eq!(next_serial, next_parallel);
+    }
+
+    // Short buffers that don't reach a block boundary.
+    let mut serial_reader = hasher.finalize_xof();
+    let mut parallel_reader = hasher.finalize_xof();
+    for &n in &[0, 10, 50, 4, 64, 1] {
+        let mut serial = [0; 64];
+        let mut parallel = [0; 64];
+        serial_reader.fill(&mut serial[..n]);
+        parallel_reader.fill_rayon(&mut parallel[..n]);
+        assert_eq!(serial, parallel);
+        assert_eq!(serial_reader.position(), parallel_reader.position());
+    }
+}
+
+#[test]
+#[cfg(feature = "rayon")]
+fn test_update_rayon_in_thread_counts() {
+    let pools: Vec<rayon::ThreadPool> = [1, 2, 4, 8]
+        .iter()
+        .map(|&n| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(n)
+                .build()
+                .unwrap()
+        })
+        .collect();
+    let mut input_buf = [0; TEST_CASES_MAX];
+    paint_test_input(&mut input_buf);
+    for &case in TEST_CASES {
+        let input = &input_buf[..case];
+        let expected = crate::hash(input);
+        for pool in &pools {
+            let mut hasher = crate::Hasher::new_keyed(&TEST_KEY);
+            hasher.update_rayon_in(input, pool);
+            let mut plain = crate::Hasher::new_keyed(&TEST_KEY);
+            plain.update(input);
+            assert_eq!(hasher.finalize(), plain.finalize());
+
+            let mut hasher = crate::Hasher::new();
+            // Split updates, so that the second one starts at an uneven chunk count.
+            let split = case / 3;
+            hasher.update_rayon_in(&input[..split], pool);
+            hasher.update_rayon_in(&input[split..], pool);
+            assert_eq!(
+                hasher.finalize(),
+                expected,
+                "case {} threads {}",
+                case,
+                pool.current_num_threads(),
+            );
+        }
+    }
+}
+
+#[test]
+fn test_hash_list() {
+    use crate::guts::parent_cv;
+    use crate::{hash, hash_list};
+    let (a, b, c): (&[u8], &[u8], &[u8]) = (b"record a", b"record b", b"record c");
+
+    assert_eq!(hash_list(&[]), hash(b""));
+    assert_eq!(hash_list(&[a]), hash(a));
+    assert_eq!(hash_list(&[a, b]), parent_cv(&hash(a), &hash(b), true));
+    assert_eq!(
+        hash_list(&[a, b, c]),
+        parent_cv(&parent_cv(&hash(a), &hash(b), false), &hash(c), true),
+    );
+    // Five leaves split four and one.
+    let four = parent_cv(
+        &parent_cv(&hash(a), &hash(b), false),
+        &parent_cv(&hash(c), &hash(a), false),
+        false,
+    );
+    assert_eq!(
+        hash_list(&[a, b, c, a, b]),
+        parent_cv(&four, &hash(b), true),
+    );
+
+    // Reordering or re-splitting the records changes the root.
+    assert_ne!(hash_list(&[a, b]), hash_list(&[b, a]));
+    assert_ne!(hash_list(&[a, b, c]), hash_list(&[a, c, b]));
+    assert_ne!(hash_list(&[b"ab", b"c"]), hash_list(&[b"a", b"bc"]));
+    assert_ne!(hash_list(&[b"a", b"b"]), hash(b"ab"));
+}
+
+#[test]
+fn test_tiny_updates() {
+    let mut input_buf = [0; 2 * CHUNK_LEN + 1];
+    paint_test_input(&mut input_buf);
+    for &case in TEST_CASES {
+        if case > input_buf.len() {
+            continue;
+        }
+        let input = &input_buf[..case];
+        let expected = crate::hash(input);
+
+        let mut hasher = crate::Hasher::new();
+        for &byte in input {
+            hasher.update(&[byte]);
+        }
+        assert_eq!(hasher.finalize(), expected, "single bytes, case {}", case);
+
+        // Uneven sizes that straddle block boundaries, mixed with empty updates.
+        let mut hasher = crate::Hasher::new();
+        let mut remaining = input;
+        for size in [0, 3, 7, 0, 60, 1].iter().cycle() {
+            let take = core::cmp::min(*size, remaining.len());
+            hasher.update(&remaining[..take]);
+            remaining = &remaining[take..];
+            if remaining.is_empty() {
+                break;
+            }
+        }
+        assert_eq!(hasher.finalize(), expected, "uneven sizes, case {}", case);
+    }
+}
+
+#[test]
+fn test_hex_encoding_decoding() {
+    let digest_str = "04e0bb39f30b1a3feb89f536c93be15055482df748674b00d26e5a75777702e9";
+    let mut hasher = crate::Hasher::new();
+    hasher.update(b"foo");
+    let digest = hasher.finalize();
+    assert_eq!(digest.to_hex().as_str(), digest_str);
+    #[cfg(feature = "std")]
+    assert_eq!(digest.to_string(), digest_str);
+
+    // Test round trip
+    let digest = crate::Hash::from_hex(digest_str).unwrap();
+    assert_eq!(digest.to_hex().as_str(), digest_str);
+
+    // Test uppercase
+    let digest = crate::Hash::from_hex(digest_str.to_uppercase()).unwrap();
+    assert_eq!(digest.to_hex().as_str(), digest_str);
+
+    // Test string parsing via FromStr
+    let digest: crate::Hash = digest_str.parse().unwrap();
+    assert_eq!(digest.to_hex().as_str(), digest_str);
+
+    // Test string parsing via TryFrom
+    let digest = crate::Hash::try_from(digest_str).unwrap();
+    assert_eq!(digest.to_hex().as_str(), digest_str);
+    let _result = crate::Hash::try_from("04e0bb39f30b1").unwrap_err();
+    #[cfg(feature = "std")]
+    assert_eq!(_result.to_string(), "expected 64 hex bytes, received 13");
+
+    // Test errors
+    let bad_len = "04e0bb39f30b1";
+    let _result = crate::Hash::from_hex(bad_len).unwrap_err();
+    #[cfg(feature = "std")]
+    assert_eq!(_result.to_string(), "expected 64 hex bytes, received 13");
+
+    let bad_char = "Z4e0bb39f30b1a3feb89f536c93be15055482df748674b00d26e5a75777702e9";
+    let _result = crate::Hash::from_hex(bad_char).unwrap_err();
+    #[cfg(feature = "std")]
+    assert_eq!(_result.to_string(), "invalid hex character: 'Z'");
+
+    let _result = crate::Hash::from_hex([128; 64]).unwrap_err();
+    #[cfg(feature = "std")]
+    assert_eq!(_result.to_string(), "invalid hex character: 0x80");
+}
+
+#[test]
+fn test_hash_eq_hex() {
+    let hash = crate::hash(b"foo");
+    let hex = hash.to_hex();
+    assert!(hash.eq_hex(&hex));
+    assert!(!crate::hash(b"bar").eq_hex(&hex));
+    // Wrong lengths.
+    assert!(!hash.eq_hex(""));
+    assert!(!hash.eq_hex(&hex[..62]));
+    assert!(!hash.eq_hex(&hex[..63]));
+    // A non-hex character in an otherwise valid string.
+    let mut bad = hex;
+    bad.truncate(63);
+    bad.push('g');
+    assert!(!hash.eq_hex(&bad));
+    // Multibyte characters in a string of the right length.
+    let mut multibyte = arrayvec::ArrayString::<64>::new();
+    multibyte.push('\u{e9}');
+    multibyte.push_str(&hex[2..]);
+    assert!(!hash.eq_hex(&multibyte));
+    #[cfg(feature = "std")]
+    {
+        assert!(hash.eq_hex(&hex.to_ascii_uppercase()));
+        assert!(!hash.eq_hex(&[hex.as_str(), "00"].concat()));
+    }
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_write_hashes_hex() {
+    let hashes = [
+        crate::hash(b"foo"),
+        crate::hash(b"bar"),
+        crate::Hash::from_bytes([0xff; 32]),
+    ];
+    let mut expected = String::new();
+    for hash in &hashes {
+        expected.push_str(&hash.to_hex());
+        expected.push('\n');
+    }
+    let mut out = Vec::new();
+    crate::write_hashes_hex(&hashes, &mut out).unwrap();
+    assert_eq!(std::str::from_utf8(&out).unwrap(), expected);
+
+    // No hashes, no output.
+    let mut out = Vec::new();
+    crate::write_hashes_hex(&[], &mut out).unwrap();
+    assert!(out.is_empty());
+}
+
+#[test]
+fn test_tagged_hex_encoding_decoding() {
+    let digest_str = "04e0bb39f30b1a3feb89f536c93be15055482df748674b00d26e5a75777702e9";
+    let digest = crate::hash(b"foo");
+    let tagged = digest.to_tagged_hex(0x1e);
+    assert_eq!(tagged.len(), 66);
+    assert_eq!(&tagged[..2], "1e");
+    assert_eq!(&tagged[2..], digest_str);
+
+    // Test round trip
+    let (tag, digest2) = crate::Hash::from_tagged_hex(tagged.as_str()).unwrap();
+    assert_eq!(tag, 0x1e);
+    assert_eq!(digest2, digest);
+
+    // Test uppercase, and a tag with the high bit set
+    #[cfg(feature = "std")]
+    {
+        let tagged = digest.to_tagged_hex(0xff).to_uppercase();
+        let (tag, digest2) = crate::Hash::from_tagged_hex(tagged).unwrap();
+        assert_eq!(tag, 0xff);
+        assert_eq!(digest2, digest);
+    }
+
+    // Test errors
+    let _result = crate::Hash::from_tagged_hex(digest_str).unwrap_err();
+    #[cfg(feature = "std")]
+    assert_eq!(_result.to_string(), "expected 66 hex bytes, received 64");
+
+    let mut bad_tag = [0; 66];
+    bad_tag[..2].copy_from_slice(b"z1");
+    bad_tag[2..].copy_from_slice(digest_str.as_bytes());
+    let _result = crate::Hash::from_tagged_hex(bad_tag).unwrap_err();
+    #[cfg(feature = "std")]
+    assert_eq!(_result.to_string(), "invalid hex character: 'z'");
+}
+
+#[test]
+fn test_multihash() {
+    let hash = crate::hash(b"foo");
+    let multihash = hash.to_multihash();
+    assert_eq!(multihash.len(), crate::MULTIHASH_LEN);
+    assert_eq!(multihash[..2], [0x1e, 0x20]);
+    assert_eq!(multihash[2..], *hash.as_bytes());
+    assert_eq!(crate::Hash::from_multihash(&multihash).unwrap(), hash);
+
+    // Wrong code.
+    let mut bad = multihash;
+    bad[0] = 0x12;
+    assert!(crate::Hash::from_multihash(&bad).is_err());
+    // Wrong digest length.
+    let mut bad = multihash;
+    bad[1] = 0x40;
+    assert!(crate::Hash::from_multihash(&bad).is_err());
+    // Wrong overall length.
+    assert!(crate::Hash::from_multihash(&multihash[..33]).is_err());
+    assert!(crate::Hash::from_multihash(&[]).is_err());
+
+    #[cfg(feature = "std")]
+    {
+        let err = crate::Hash::from_multihash(&bad).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "expected multihash digest length 32, found 64"
+        );
+    }
+}
+
+#[test]
+fn test_base32_encoding_decoding() {
+    let digest_str = "ATQLWOPTBMND724J6U3MSO7BKBKUQLPXJBTUWAGSNZNHK53XALUQ";
+    let digest = crate::hash(b"foo");
+    assert_eq!(digest.to_base32().as_str(), digest_str);
+
+    // Test round trip
+    let digest2 = crate::Hash::from_base32(digest_str).unwrap();
+    assert_eq!(digest2, digest);
+
+    // Test lowercase
+    let mut lowercase = [0; 52];
+    lowercase.copy_from_slice(digest_str.as_bytes());
+    lowercase.make_ascii_lowercase();
+    assert_eq!(crate::Hash::from_base32(lowercase).unwrap(), digest);
+
+    // Test all possible byte values
+    let mut bytes = [0; 32];
+    for i in 0..=255u8 {
+        bytes.fill(i);
+        let hash = crate::Hash::from_bytes(bytes);
+        assert_eq!(
+            crate::Hash::from_base32(hash.to_base32().as_str()).unwrap(),
+            hash
+        );
+    }
+
+    // Test errors
+    let _result = crate::Hash::from_base32(&digest_str[..51]).unwrap_err();
+    #[cfg(feature = "std")]
+    assert_eq!(_result.to_string(), "expected 52 base32 bytes, received 51");
+
+    let mut padded = [b'='; 56];
+    padded[..52].copy_from_slice(digest_str.as_bytes());
+    let _result = crate::Hash::from_base32(padded).unwrap_err();
+    #[cfg(feature = "std")]
+    assert_eq!(_result.to_string(), "expected 52 base32 bytes, received 56");
+
+    // '1' and '8' aren't in the alphabet.
+    let mut bad_char = lowercase;
+    bad_char[0] = b'1';
+    let _result = crate::Hash::from_base32(bad_char).unwrap_err();
+    #[cfg(feature = "std")]
+    assert_eq!(_result.to_string(), "invalid base32 character: '1'");
+
+    bad_char[0] = 0x80;
+    let _result = crate::Hash::from_base32(bad_char).unwrap_err();
+    #[cfg(feature = "std")]
+    assert_eq!(_result.to_string(), "invalid base32 character: 0x80");
+
+    // The final 'Q' has a zero low bit. 'R' decodes to the same bytes but sets it.
+    let mut trailing_bits = lowercase;
+    trailing_bits[51] = b'R';
+    let _result = crate::Hash::from_base32(trailing_bits).unwrap_err();
+    #[cfg(feature = "std")]
+    assert_eq!(
+        _result.to_string(),
+        "nonzero trailing bits in final base32 character: 'R'",
+    );
+}
+
+#[test]
+#[cfg(feature = "rand")]
+fn test_hash_random() {
+    use rand::SeedableRng;
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(42);
+    let hash1 = crate::Hash::random(&mut rng);
+    let hash2 = crate::Hash::random(&mut rng);
+    assert_ne!(hash1, hash2);
+
+    // The same seed gives the same hashes, which are just the RNG's output bytes.
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(42);
+    assert_eq!(crate::Hash::random(&mut rng), hash1);
+    let mut expected = [0; 32];
+    rng.fill_bytes(&mut expected);
+    assert_eq!(hash2, expected);
+}
+
+#[test]
+fn test_hash_debug() {
+    use core::fmt::Write;
+    // Format into an ArrayString, so that this works without std.
+    let mut debug = arrayvec::ArrayString::<80>::new();
+    write!(debug, "{:?}", crate::hash(b"foo")).unwrap();
+    assert_eq!(
+        debug.as_str(),
+        "Hash(\"04e0bb39f30b1a3feb89f536c93be15055482df748674b00d26e5a75777702e9\")",
+    );
+}
+
+#[test]
+#[cfg(feature = "test-util")]
+fn test_assert_hash_eq_equal() {
+    let hash = crate::hash(b"foo");
+    crate::assert_hash_eq!(hash, crate::Hasher::new().update(b"foo").finalize());
+    crate::assert_hash_eq!(hash, hash, "with a message {}", 42);
+}
+
+#[test]
+#[cfg(all(feature = "test-util", feature = "std"))]
+fn test_assert_hash_eq_unequal() {
+    let expected = crate::hash(b"foo");
+    let mut bytes = *expected.as_bytes();
+    bytes[0] ^= 0x10;
+    bytes[31] ^= 1;
+    let actual = crate::Hash::from(bytes);
+    let panic = std::panic::catch_unwind(|| {
+        crate::assert_hash_eq!(expected, actual, "case {}", 7);
+    })
+    .unwrap_err();
+    let message = panic.downcast_ref::<String>().unwrap();
+    let lines: Vec<&str> = message.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[0], "hashes not equal: case 7");
+    assert_eq!(lines[1], format!("expected: {}", expected));
+    assert_eq!(lines[2], format!("  actual: {}", actual));
+    assert_eq!(lines[3], format!("          ^{}^", " ".repeat(62)));
+}
+
+// This test is a mimized failure case for the Windows SSE2 bug described in
+// https://github.com/BLAKE3-team/BLAKE3/issues/206.
+//
+// Before that issue was fixed, this test would fail on Windows in the following configuration:
+//
+//     cargo test --features=no_avx512,no_avx2,no_sse41 --release
+//
+// Bugs like this one (stomping on a caller's register) are very sensitive to the details of
+// surrounding code, so it's not especially likely that this test will catch another bug (or even
+// the same bug) in the future. Still, there's no harm in keeping it.
+#[test]
+#[allow(clippy::single_element_loop)]
+fn test_issue_206_windows_sse2() {
+    // This stupid loop has to be here to trigger the bug. I don't know why.
+    for _ in &[0] {
+        // The length 65 (two blocks) is significant. It doesn't repro with 64 (one block). It also
+        // doesn't repro with an all-zero input.
+        let input = &[0xff; 65];
+        let expected_hash = [
+            183, 235, 50, 217, 156, 24, 190, 219, 2, 216, 176, 255, 224, 53, 28, 95, 57, 148, 179,
+            245, 162, 90, 37, 121, 0, 142, 219, 62, 234, 204, 225, 161,
+        ];
+
+        // This throwaway call has to be here to trigger the bug.
+        crate::Hasher::new().update(input);
+
+        // This assert fails when the bug is triggered.
+        assert_eq!(crate::Hasher::new().update(input).finalize(), expected_hash);
+    }
+}
+
+#[test]
+fn test_hash_conversions() {
+    let bytes1 = [42; 32];
+    let hash1: crate::Hash = bytes1.into();
+    let bytes2: [u8; 32] = hash1.into();
+    assert_eq!(bytes1, bytes2);
 
     let bytes3 = *hash1.as_bytes();
     assert_eq!(bytes1, bytes3);
@@ -622,6 +1873,145 @@ fn test_hash_conversions() {
     assert_eq!(hash1, hash3);
 }
 
+#[test]
+fn test_hash_empty() {
+    const EMPTY: crate::Hash = crate::Hash::EMPTY;
+    assert_eq!(EMPTY, crate::hash(&[]));
+    assert_eq!(EMPTY, crate::Hasher::new().finalize());
+    assert_eq!(
+        EMPTY.to_hex().as_str(),
+        "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262"
+    );
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_hash_from_reader() {
+    let hash1 = crate::hash(b"foo");
+    let hash2 = crate::hash(b"bar");
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(hash1.as_bytes());
+    bytes.extend_from_slice(hash2.as_bytes());
+    bytes.push(42);
+
+    let mut cursor = std::io::Cursor::new(bytes);
+    assert_eq!(crate::Hash::from_reader(&mut cursor).unwrap(), hash1);
+    assert_eq!(crate::Hash::from_reader(&mut cursor).unwrap(), hash2);
+    assert_eq!(cursor.position(), 64);
+
+    // Only one byte left.
+    let err = crate::Hash::from_reader(&mut cursor).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_hash_write_to() {
+    let hash1 = crate::hash(b"foo");
+    let hash2 = crate::hash(b"bar");
+    let mut bytes = Vec::new();
+    hash1.write_to(&mut bytes).unwrap();
+    assert_eq!(&bytes[..], hash1.as_bytes());
+    hash2.write_to(&mut bytes).unwrap();
+    assert_eq!(&bytes[32..], hash2.as_bytes());
+
+    // Round trip through from_reader.
+    let mut cursor = std::io::Cursor::new(bytes);
+    assert_eq!(crate::Hash::from_reader(&mut cursor).unwrap(), hash1);
+    assert_eq!(crate::Hash::from_reader(&mut cursor).unwrap(), hash2);
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_core_hash_impl() {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    // DefaultHasher::new() always uses the same keys.
+    fn default_hash(value: &impl Hash) -> u64 {
+        let mut state = DefaultHasher::new();
+        value.hash(&mut state);
+        state.finish()
+    }
+
+    let hash = crate::hash(b"foo");
+    assert_eq!(default_hash(&hash), default_hash(&hash));
+    assert_eq!(default_hash(&hash), default_hash(&crate::hash(b"foo")));
+    assert_ne!(default_hash(&hash), default_hash(&crate::hash(b"bar")));
+
+    // Only the raw bytes are written, with no length prefix.
+    let mut state = DefaultHasher::new();
+    state.write(hash.as_bytes());
+    assert_eq!(default_hash(&hash), state.finish());
+}
+
+#[test]
+fn test_hash_hamming_distance() {
+    let hash = crate::hash(b"foo");
+    assert_eq!(hash.hamming_distance(&hash), 0);
+
+    let mut bytes = *hash.as_bytes();
+    bytes[0] ^= 0b1000_0001;
+    bytes[17] ^= 0b0001_0000;
+    bytes[31] ^= 0xff;
+    let other = crate::Hash::from_bytes(bytes);
+    assert_eq!(hash.hamming_distance(&other), 11);
+    assert_eq!(other.hamming_distance(&hash), 11);
+
+    let zeros = crate::Hash::from_bytes([0; 32]);
+    let ones = crate::Hash::from_bytes([0xff; 32]);
+    assert_eq!(zeros.hamming_distance(&ones), 256);
+}
+
+#[test]
+fn test_ct_eq() {
+    use rand::prelude::*;
+    let mut rng = rand_chacha::ChaCha8Rng::from_seed([3; 32]);
+    for i in 0..1000 {
+        let mut a = [0; OUT_LEN];
+        rng.fill_bytes(&mut a);
+        let mut b = a;
+        // Leave every fourth pair equal, and flip one random bit in the others.
+        if i % 4 != 0 {
+            let bit = rng.gen_range(0..8 * OUT_LEN);
+            b[bit / 8] ^= 1 << (bit % 8);
+        }
+        let (a, b) = (crate::Hash::from(a), crate::Hash::from(b));
+        assert_eq!(crate::ct::eq(&a, &b), a == b);
+        assert_eq!(crate::ct::eq(&a, &b), a.as_bytes() == b.as_bytes());
+        assert_eq!(crate::ct::verify_tag(&a, &b).is_ok(), a == b);
+    }
+    let a = crate::hash(b"a");
+    assert_eq!(crate::ct::verify_tag(&a, &a), Ok(()));
+    assert_eq!(
+        crate::ct::verify_tag(&a, &crate::hash(b"b")),
+        Err(crate::ct::MacError)
+    );
+}
+
+#[test]
+fn test_hash_u128_pair() {
+    // "04e0bb39f30b1a3feb89f536c93be15055482df748674b00d26e5a75777702e9"
+    let hash = crate::hash(b"foo");
+    let (high, low) = hash.to_u128_pair();
+    assert_eq!(high, 0x04e0bb39f30b1a3feb89f536c93be150);
+    assert_eq!(low, 0x55482df748674b00d26e5a75777702e9);
+    assert_eq!(crate::Hash::from_u128_pair((high, low)), hash);
+
+    let pair = (u128::MAX - 1, 1);
+    let hash = crate::Hash::from_u128_pair(pair);
+    assert_eq!(hash.as_bytes()[15], 0xfe);
+    assert_eq!(hash.as_bytes()[31], 0x01);
+    assert_eq!(hash.to_u128_pair(), pair);
+}
+
+#[test]
+fn test_hash_truncate() {
+    let hash = crate::hash(b"foo");
+    assert_eq!(hash.truncate_128(), hash.as_bytes()[..16]);
+    assert_eq!(hash.truncate_64(), hash.as_bytes()[..8]);
+}
+
 #[test]
 const fn test_hash_const_conversions() {
     let bytes = [42; 32];
@@ -650,6 +2040,7 @@ fn test_zeroize() {
         },
         key: [42; 8],
         cv_stack: [[42; 32]; { crate::MAX_DEPTH + 1 }].into(),
+        max_input: Some(42),
     };
     hasher.zeroize();
     assert_eq!(hasher.chunk_state.cv, [0; 8]);
@@ -664,6 +2055,7 @@ fn test_zeroize() {
     ));
     assert_eq!(hasher.key, [0; 8]);
     assert_eq!(&*hasher.cv_stack, &[[0u8; 32]; 0]);
+    assert_eq!(hasher.max_input, None);
 
     let mut output_reader = crate::OutputReader {
         inner: crate::Output {
@@ -675,6 +2067,7 @@ fn test_zeroize() {
             platform: crate::Platform::Portable,
         },
         position_within_block: 42,
+        block_cache: Some([42; 64]),
     };
 
     output_reader.zeroize();
@@ -688,6 +2081,7 @@ fn test_zeroize() {
         crate::Platform::Portable
     ));
     assert_eq!(output_reader.position_within_block, 0);
+    assert_eq!(output_reader.block_cache, None);
 }
 
 #[test]
@@ -704,6 +2098,20 @@ fn test_update_reader() -> Result<(), std::io::Error> {
     Ok(())
 }
 
+#[test]
+#[cfg(feature = "std")]
+fn test_update_dyn_reader() -> Result<(), std::io::Error> {
+    let mut input = vec![0; 100_000];
+    paint_test_input(&mut input);
+    let mut reader: Box<dyn std::io::Read> = Box::new(&input[..]);
+    let reader: &mut dyn std::io::Read = &mut reader;
+    assert_eq!(
+        crate::Hasher::new().update_dyn_reader(reader)?.finalize(),
+        crate::hash(&input),
+    );
+    Ok(())
+}
+
 #[test]
 #[cfg(feature = "std")]
 fn test_update_reader_interrupted() -> std::io::Result<()> {
@@ -738,9 +2146,262 @@ fn test_update_reader_interrupted() -> std::io::Result<()> {
     let mut hasher = crate::Hasher::new();
     hasher.update_reader(&mut reader)?;
     assert_eq!(hasher.finalize(), crate::hash(input));
+
+    let mut reader = InterruptingReader::new(input);
+    let mut hasher = crate::Hasher::new();
+    hasher.update_reader_buf(&mut reader, &mut [0; 4])?;
+    assert_eq!(hasher.finalize(), crate::hash(input));
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_update_reader_timeout() {
+    use std::time::{Duration, Instant};
+
+    // Sends its input a few bytes at a time, with an interruption, and then stalls forever
+    // without reaching EOF.
+    struct StallingReader<'a> {
+        input: &'a [u8],
+        interrupted: bool,
+    }
+
+    impl std::io::Read for StallingReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.input.is_empty() {
+                return Err(std::io::ErrorKind::WouldBlock.into());
+            }
+            if !self.interrupted {
+                self.interrupted = true;
+                return Err(std::io::ErrorKind::Interrupted.into());
+            }
+            let take = buf.len().min(self.input.len()).min(1000);
+            buf[..take].copy_from_slice(&self.input[..take]);
+            self.input = &self.input[take..];
+            Ok(take)
+        }
+    }
+
+    let mut input = vec![0; 3 * CHUNK_LEN + 1];
+    paint_test_input(&mut input);
+    let mut hasher = crate::Hasher::new();
+    let reader = StallingReader {
+        input: &input,
+        interrupted: false,
+    };
+    let err = hasher
+        .update_reader_timeout(reader, Instant::now() + Duration::from_millis(20))
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+    // Everything that arrived before the stall was hashed, and the hasher can keep going.
+    assert_eq!(hasher.finalize(), crate::hash(&input));
+    hasher.update(b"more");
+    let mut expected = crate::Hasher::new();
+    expected.update(&input).update(b"more");
+    assert_eq!(hasher.finalize(), expected.finalize());
+
+    // A reader that reaches EOF in time works like update_reader.
+    let mut hasher = crate::Hasher::new();
+    hasher
+        .update_reader_timeout(&input[..], Instant::now() + Duration::from_secs(60))
+        .unwrap();
+    assert_eq!(hasher.finalize(), crate::hash(&input));
+
+    // A deadline that's already passed doesn't read anything.
+    let mut hasher = crate::Hasher::new();
+    let err = hasher
+        .update_reader_timeout(&input[..], Instant::now())
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+    assert_eq!(hasher.count(), 0);
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_update_reader_buf() -> std::io::Result<()> {
+    let mut input = vec![0; 3 * CHUNK_LEN + 1];
+    paint_test_input(&mut input);
+    // A 7-byte scratch buffer takes many reads, none of them block-aligned.
+    let mut scratch = [0; 7];
+    let mut hasher = crate::Hasher::new();
+    hasher.update_reader_buf(&input[..], &mut scratch)?;
+    assert_eq!(hasher.finalize(), crate::hash(&input));
+
+    // The same scratch buffer can be reused, and its old contents don't matter.
+    let mut hasher = crate::Hasher::new();
+    hasher.update_reader_buf(&input[..100], &mut scratch)?;
+    assert_eq!(hasher.finalize(), crate::hash(&input[..100]));
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "std")]
+#[should_panic]
+fn test_update_reader_buf_empty_scratch() {
+    let _ = crate::Hasher::new().update_reader_buf(&b"foo"[..], &mut []);
+}
+
+#[test]
+#[cfg(feature = "bytes")]
+fn test_update_buf() {
+    use bytes::Buf;
+
+    let mut input = [0; 3 * CHUNK_LEN + 7];
+    paint_test_input(&mut input);
+    let (a, rest) = input.split_at(BLOCK_LEN + 1);
+    let (b, c) = rest.split_at(CHUNK_LEN);
+
+    let mut chained = a.chain(b).chain(c);
+    let mut hasher = crate::Hasher::new();
+    hasher.update_buf(&mut chained);
+    assert!(!chained.has_remaining());
+    assert_eq!(hasher.finalize(), crate::hash(&input));
+
+    // A partially consumed buffer only contributes what's left.
+    let mut partial = a.chain(b);
+    partial.advance(10);
+    let mut hasher = crate::Hasher::new();
+    hasher.update_buf(&mut partial);
+    assert_eq!(
+        hasher.finalize(),
+        crate::hash(&input[10..a.len() + b.len()])
+    );
+}
+
+#[test]
+#[cfg(feature = "std")]
+// NamedTempFile isn't Miri-compatible
+#[cfg(not(miri))]
+fn test_update_file_with_len() -> Result<(), std::io::Error> {
+    use std::io::prelude::*;
+    let mut input = vec![0; 100_000];
+    paint_test_input(&mut input);
+    let mut file1 = tempfile::NamedTempFile::new()?;
+    file1.write_all(&input)?;
+    file1.flush()?;
+    let mut file2 = tempfile::NamedTempFile::new()?;
+    file2.write_all(&input)?;
+    file2.flush()?;
+
+    // The documented layout.
+    let mut expected = crate::Hasher::new();
+    expected.update(b"filelen\xff");
+    expected.update(&(input.len() as u64).to_le_bytes());
+    expected.update(&input);
+    let hash1 = crate::Hasher::new()
+        .update_file_with_len(&std::fs::File::open(file1.path())?)?
+        .finalize();
+    assert_eq!(hash1, expected.finalize());
+    assert!(u64::from_le_bytes(*crate::FILE_WITH_LEN_TAG) > isize::MAX as u64);
+
+    // The tag keeps this distinct from framing the contents.
+    let mut framed = crate::Hasher::new();
+    framed.update_framed(&input);
+    assert_ne!(hash1, framed.finalize());
+
+    // Identical files match.
+    let hash2 = crate::Hasher::new()
+        .update_file_with_len(&std::fs::File::open(file2.path())?)?
+        .finalize();
+    assert_eq!(hash1, hash2);
+
+    // A file that isn't at its start is an error, rather than a hash of part of it, and nothing
+    // gets hashed.
+    let mut file = std::fs::File::open(file1.path())?;
+    file.seek(std::io::SeekFrom::Start(10))?;
+    let mut hasher = crate::Hasher::new();
+    let err = hasher.update_file_with_len(&file).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    assert_eq!(hasher.count(), 0);
+    file.seek(std::io::SeekFrom::Start(0))?;
+    assert_eq!(hasher.update_file_with_len(&file)?.finalize(), hash1);
+
+    // Growing after the length is read is an error. Simulate that with a reader that's longer
+    // than the length.
+    let mut longer = input.clone();
+    longer.push(0);
+    let err = crate::io::copy_exact(&longer[..], &mut crate::Hasher::new(), input.len() as u64)
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    // A truncated file with the missing bytes appended separately doesn't
+    // collide with the original, as it would without the length prefix.
+    let truncated_len = 1000;
+    file2.as_file().set_len(truncated_len as u64)?;
+    let mut truncated = crate::Hasher::new();
+    truncated.update_file_with_len(&std::fs::File::open(file2.path())?)?;
+    truncated.update(&input[truncated_len..]);
+    assert_ne!(hash1, truncated.finalize());
+    let mut truncated_plain = crate::Hasher::new();
+    truncated_plain.update_reader(std::fs::File::open(file2.path())?)?;
+    truncated_plain.update(&input[truncated_len..]);
+    assert_eq!(truncated_plain.finalize(), crate::hash(&input));
     Ok(())
 }
 
+#[test]
+#[cfg(feature = "std")]
+#[cfg(not(miri))]
+fn test_hash_dir() {
+    use std::fs;
+
+    // Create the same tree twice, with files and directories created in opposite orders, so
+    // that the filesystem is likely to list them differently.
+    let files: &[(&str, &[u8])] = &[
+        ("a", b"first"),
+        ("a-b", b""),
+        ("b/c", b"nested"),
+        ("b/d/e", &[7; 2 * CHUNK_LEN + 1]),
+        ("z", b"last"),
+    ];
+    let forward = tempfile::tempdir().unwrap();
+    let backward = tempfile::tempdir().unwrap();
+    for (root, order) in [
+        (&forward, files.iter().collect::<Vec<_>>()),
+        (&backward, files.iter().rev().collect()),
+    ] {
+        for (path, contents) in order {
+            let full_path = root.path().join(path);
+            fs::create_dir_all(full_path.parent().unwrap()).unwrap();
+            fs::write(full_path, contents).unwrap();
+        }
+    }
+    fs::create_dir(backward.path().join("empty")).unwrap();
+    let hash = crate::hash_dir(forward.path()).unwrap();
+    assert_eq!(hash, crate::hash_dir(backward.path()).unwrap());
+
+    // Compute the expected manifest by hand. "a-b" sorts before "b/c", and "b/c" before
+    // "b/d/e", by bytes.
+    let mut expected = crate::Hasher::new();
+    for (path, contents) in files {
+        expected.update_framed(path.as_bytes());
+        expected.update_framed(contents);
+    }
+    assert_eq!(hash, expected.finalize());
+
+    // Changing a file's contents or name changes the result.
+    fs::write(forward.path().join("b/c"), b"nesteD").unwrap();
+    assert_ne!(hash, crate::hash_dir(forward.path()).unwrap());
+    fs::write(forward.path().join("b/c"), b"nested").unwrap();
+    assert_eq!(hash, crate::hash_dir(forward.path()).unwrap());
+    fs::rename(forward.path().join("z"), forward.path().join("y")).unwrap();
+    assert_ne!(hash, crate::hash_dir(forward.path()).unwrap());
+
+    // The contents have to match the length exactly. A file that shrinks or grows after its
+    // length is read is an error, rather than hashing a cut-off copy.
+    let mut hasher = crate::Hasher::new();
+    crate::io::copy_exact(&b"abc"[..], &mut hasher, 3).unwrap();
+    assert_eq!(hasher.finalize(), crate::hash(b"abc"));
+    let err = crate::io::copy_exact(&b"abc"[..], &mut hasher, 4).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    let err = crate::io::copy_exact(&b"abc"[..], &mut hasher, 2).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    // An empty directory hashes to the empty manifest.
+    let empty = tempfile::tempdir().unwrap();
+    assert_eq!(crate::hash_dir(empty.path()).unwrap(), crate::hash(b""));
+}
+
 #[test]
 #[cfg(feature = "mmap")]
 // NamedTempFile isn't Miri-compatible
@@ -805,6 +2466,31 @@ fn test_mmap_rayon() -> Result<(), std::io::Error> {
     Ok(())
 }
 
+#[test]
+#[cfg(feature = "mmap")]
+// NamedTempFile isn't Miri-compatible
+#[cfg(not(miri))]
+fn test_mmap_ref() -> Result<(), std::io::Error> {
+    use std::io::prelude::*;
+    let mut input = vec![0; 1_000_000];
+    paint_test_input(&mut input);
+    let mut tempfile = tempfile::NamedTempFile::new()?;
+    tempfile.write_all(&input)?;
+    tempfile.flush()?;
+    let mmap = unsafe { memmap2::Mmap::map(tempfile.as_file())? };
+    let expected = crate::hash(&input);
+    assert_eq!(
+        crate::Hasher::new().update_mmap_ref(&mmap).finalize(),
+        expected
+    );
+    #[cfg(feature = "rayon")]
+    assert_eq!(
+        crate::Hasher::new().update_mmap_ref_rayon(&mmap).finalize(),
+        expected,
+    );
+    Ok(())
+}
+
 #[test]
 #[cfg(feature = "std")]
 #[cfg(feature = "serde")]
@@ -819,6 +2505,24 @@ fn test_serde() {
     assert_eq!(hash, hash2);
 }
 
+#[test]
+#[cfg(feature = "serde")]
+fn test_short_hash_serde() {
+    let short = crate::Hasher::new().update(b"foo").finalize_short::<20>();
+    let json = serde_json::to_string(&short).unwrap();
+    let short2: crate::ShortHash<20> = serde_json::from_str(&json).unwrap();
+    assert_eq!(short, short2);
+    assert!(serde_json::from_str::<crate::ShortHash<21>>(&json).is_err());
+
+    // ShortHash<32> serializes the same way as Hash.
+    let hash = crate::hash(b"foo");
+    let full: crate::ShortHash<32> = crate::ShortHash::from(*hash.as_bytes());
+    assert_eq!(
+        serde_json::to_string(&full).unwrap(),
+        serde_json::to_string(&hash).unwrap()
+    );
+}
+
 // `cargo +nightly miri test` currently works, but it takes forever, because some of our test
 // inputs are quite large. Most of our unsafe code is platform specific and incompatible with Miri
 // anyway, but we'd like it to be possible for callers to run their own tests under Miri, assuming
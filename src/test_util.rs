@@ -0,0 +1,66 @@
+//! Helpers for testing code that produces BLAKE3 hashes.
+//!
+//! See [`assert_hash_eq!`](crate::assert_hash_eq).
+
+use crate::Hash;
+use arrayvec::ArrayString;
+use core::fmt;
+
+/// Assert that two [`Hash`]es are equal, and panic with both of them in hex if they're not.
+///
+/// The panic message lines up the expected and actual hashes and marks the hex digits that
+/// differ, which is easier to read than the default `assert_eq!` output. Like `assert_eq!`, an
+/// optional format string and arguments can be added after the two hashes.
+///
+/// This macro requires the `test-util` Cargo feature, which is disabled by default but enabled on
+/// [docs.rs](https://docs.rs).
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(feature = "test-util")]
+/// # {
+/// let expected = blake3::hash(b"foo");
+/// let actual = blake3::Hasher::new().update(b"foo").finalize();
+/// blake3::assert_hash_eq!(expected, actual);
+/// blake3::assert_hash_eq!(expected, actual, "hashing {:?}", "foo");
+/// # }
+/// ```
+#[macro_export]
+macro_rules! assert_hash_eq {
+    ($expected:expr, $actual:expr $(,)?) => {
+        $crate::test_util::assert_hash_eq(&$expected, &$actual, ::core::option::Option::None)
+    };
+    ($expected:expr, $actual:expr, $($arg:tt)+) => {
+        $crate::test_util::assert_hash_eq(
+            &$expected,
+            &$actual,
+            ::core::option::Option::Some(::core::format_args!($($arg)+)),
+        )
+    };
+}
+
+/// The function behind [`assert_hash_eq!`](crate::assert_hash_eq). Prefer the macro.
+#[track_caller]
+pub fn assert_hash_eq(expected: &Hash, actual: &Hash, message: Option<fmt::Arguments>) {
+    if expected == actual {
+        return;
+    }
+    let expected_hex = expected.to_hex();
+    let actual_hex = actual.to_hex();
+    let mut markers = ArrayString::<{ 2 * crate::OUT_LEN }>::new();
+    for (e, a) in expected_hex.bytes().zip(actual_hex.bytes()) {
+        markers.push(if e == a { ' ' } else { '^' });
+    }
+    let markers = markers.trim_end();
+    match message {
+        Some(message) => panic!(
+            "hashes not equal: {}\nexpected: {}\n  actual: {}\n          {}",
+            message, expected_hex, actual_hex, markers,
+        ),
+        None => panic!(
+            "hashes not equal\nexpected: {}\n  actual: {}\n          {}",
+            expected_hex, actual_hex, markers,
+        ),
+    }
+}